@@ -0,0 +1,11 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+use brain_rust::{parse, OpStream};
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(ops) = parse(data) {
+        OpStream { ops }.optimize();
+    }
+});
@@ -0,0 +1,32 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+use brain_rust::{parse, Config, Eof, OpStream, State};
+
+const CONFIG: Config = Config {
+    max_steps: Some(100_000),
+    tape_size: Some(1 << 16),
+    eof: Eof::Stop,
+};
+
+// Everything after the first `!` is the program's input.
+fuzz_target!(|data: &[u8]| {
+    let (program, input) = match data.iter().position(|&c| c == b'!') {
+        Some(i) => (&data[..i], &data[i + 1..]),
+        None => (data, &[][..]),
+    };
+    let mut opstream = match parse(program) {
+        Ok(ops) => OpStream { ops },
+        Err(_) => return,
+    };
+    for optimize in [false, true] {
+        if optimize {
+            opstream.optimize();
+        }
+        let mut input = input;
+        let mut output = vec![];
+        let mut state = State::with_config(&mut input, &mut output, &CONFIG);
+        let _ = state.run(opstream.get());
+    }
+});
@@ -0,0 +1,399 @@
+use std::fmt::Write;
+
+use crate::optimizer::transfer_factor;
+use crate::runner::{Config, Eof};
+use crate::structs::Op::*;
+use crate::structs::{contains, Op};
+
+const TAPE_SIZE: usize = 30000;
+
+// Offsets on the tape have to fit into instructions.
+const MAX_TAPE_SIZE: usize = i32::MAX as usize;
+
+/// Emits GNU assembler source for x86-64 Linux with the same behaviour as the
+/// operations. It defines `_start` and uses no library, only the `read`,
+/// `write` and `exit` system calls, so it links with
+/// `cc -nostdlib -static`. The tape is in BSS and has the configured size,
+/// 30000 cells by default, and the program stops with an error when the
+/// pointer leaves it.
+///
+/// # Panics
+///
+/// If the configured tape has 2 GiB or more.
+pub fn emit_asm(ops: &[Op], config: &Config) -> String {
+    let tape_size = config.tape_size.unwrap_or(TAPE_SIZE);
+    assert!(
+        tape_size <= MAX_TAPE_SIZE,
+        "The tape is too large for x86-64"
+    );
+    let mut emitter = Emitter {
+        out: String::new(),
+        labels: 0,
+        tape_size,
+        checks: false,
+    };
+    emitter.emit(ops);
+
+    let mut out = String::new();
+    writeln!(
+        out,
+        concat!(
+            "# Generated by brain_rust.\n",
+            "# %r12 holds the address of the tape and %rbx the pointer.\n",
+            "\n",
+            "\t.set TAPE_SIZE, {}\n",
+            "\n",
+            "\t.bss\n",
+            "tape:\n",
+            "\t.zero TAPE_SIZE\n",
+            "\n",
+            "\t.text\n",
+            "\t.globl _start\n",
+            "_start:\n",
+            "\tlea tape(%rip), %r12\n",
+            "\txor %ebx, %ebx\n",
+            "{}",
+            "\tmov $60, %eax\n",
+            "\txor %edi, %edi\n",
+            "\tsyscall"
+        ),
+        tape_size, emitter.out
+    )
+    .unwrap();
+
+    if contains(ops, &|op| matches!(op, In)) {
+        let eof = match config.eof {
+            Eof::Stop => "\tmov $60, %eax\n\txor %edi, %edi\n\tsyscall\n",
+            Eof::Unchanged => "\tret\n",
+            Eof::Zero => "\tmovb $0, (%r12,%rbx)\n\tret\n",
+            Eof::MinusOne => "\tmovb $255, (%r12,%rbx)\n\tret\n",
+        };
+        write!(
+            out,
+            concat!(
+                "\n",
+                "# Reads a byte into the current cell.\n",
+                "read_cell:\n",
+                "\txor %eax, %eax\n",
+                "\txor %edi, %edi\n",
+                "\tlea (%r12,%rbx), %rsi\n",
+                "\tmov $1, %edx\n",
+                "\tsyscall\n",
+                "\ttest %rax, %rax\n",
+                "\tjs io_error\n",
+                "\tjz .Leof\n",
+                "\tret\n",
+                ".Leof:\n",
+                "{}",
+            ),
+            eof
+        )
+        .unwrap();
+    }
+
+    if contains(ops, &|op| matches!(op, Out)) {
+        out.push_str(concat!(
+            "\n",
+            "# Writes the current cell.\n",
+            "write_cell:\n",
+            "\tmov $1, %eax\n",
+            "\tmov $1, %edi\n",
+            "\tlea (%r12,%rbx), %rsi\n",
+            "\tmov $1, %edx\n",
+            "\tsyscall\n",
+            "\ttest %rax, %rax\n",
+            "\tjs io_error\n",
+            "\tret\n",
+        ));
+    }
+
+    if contains(ops, &|op| matches!(op, In | Out)) {
+        out.push_str(concat!(
+            "\n",
+            "io_error:\n",
+            "\tmov $60, %eax\n",
+            "\tmov $1, %edi\n",
+            "\tsyscall\n",
+        ));
+    }
+
+    if emitter.checks {
+        out.push_str(concat!(
+            "\n",
+            "off_tape:\n",
+            "\tmov $1, %eax\n",
+            "\tmov $2, %edi\n",
+            "\tlea message(%rip), %rsi\n",
+            "\tmov $message_size, %edx\n",
+            "\tsyscall\n",
+            "\tmov $60, %eax\n",
+            "\tmov $1, %edi\n",
+            "\tsyscall\n",
+            "\n",
+            "\t.section .rodata\n",
+            "message:\n",
+            "\t.ascii \"Pointer moved off the tape\\n\"\n",
+            "\t.set message_size, . - message\n",
+        ));
+    }
+
+    out.push_str("\n\t.section .note.GNU-stack,\"\",@progbits\n");
+    out
+}
+
+fn cell(offset: isize) -> String {
+    match offset {
+        0 => "(%r12,%rbx)".to_string(),
+        k => format!("{}(%r12,%rbx)", k),
+    }
+}
+
+struct Emitter {
+    out: String,
+    labels: usize,
+    tape_size: usize,
+    checks: bool,
+}
+
+impl Emitter {
+    fn line(&mut self, line: &str) {
+        self.out.push('\t');
+        self.out.push_str(line);
+        self.out.push('\n');
+    }
+
+    fn label(&mut self) -> usize {
+        self.labels += 1;
+        self.labels
+    }
+
+    fn add(&mut self, offset: isize, value: u8) {
+        let line = match value {
+            1 => format!("incb {}", cell(offset)),
+            0xff => format!("decb {}", cell(offset)),
+            v if v < 0x80 => format!("addb ${}, {}", v, cell(offset)),
+            v => format!("subb ${}, {}", v.wrapping_neg(), cell(offset)),
+        };
+        self.line(&line);
+    }
+
+    /// Jumps to `off_tape` unless the cell at `offset` from the pointer is on
+    /// the tape. Returns `false` if it never is, so that the offset can't be
+    /// used.
+    fn check(&mut self, offset: isize) -> bool {
+        self.checks = true;
+        if offset.unsigned_abs() >= self.tape_size {
+            self.line("jmp off_tape");
+            return false;
+        }
+        if offset < 0 {
+            self.line(&format!("cmp ${}, %rbx", offset.unsigned_abs()));
+            self.line("jb off_tape");
+        } else {
+            self.line(&format!("cmp $TAPE_SIZE-{}, %rbx", offset));
+            self.line("jae off_tape");
+        }
+        true
+    }
+
+    fn emit(&mut self, ops: &[Op]) {
+        for op in ops {
+            match *op {
+                Add(i) => self.add(0, i),
+                Mov(n) => {
+                    if self.check(n) {
+                        let instruction = if n < 0 { "sub" } else { "add" };
+                        self.line(&format!("{} ${}, %rbx", instruction, n.unsigned_abs()));
+                    }
+                }
+                In => self.line("call read_cell"),
+                Out => self.line("call write_cell"),
+                Loop(ref stream) => {
+                    let label = self.label();
+                    self.line("cmpb $0, (%r12,%rbx)");
+                    self.line(&format!("je .Lend{}", label));
+                    self.out.push_str(&format!(".Lloop{}:\n", label));
+                    self.emit(stream.get());
+                    self.line("cmpb $0, (%r12,%rbx)");
+                    self.line(&format!("jne .Lloop{}", label));
+                    self.out.push_str(&format!(".Lend{}:\n", label));
+                }
+                Transfer(d, ref map) => self.emit_transfer(d, map),
+            }
+        }
+    }
+
+    fn emit_transfer(&mut self, d: u8, map: &[(isize, u8)]) {
+        // Even steps may stall, so they stay loops.
+        let Some(factor) = transfer_factor(d) else {
+            let label = self.label();
+            self.line("cmpb $0, (%r12,%rbx)");
+            self.line(&format!("je .Lend{}", label));
+            self.out.push_str(&format!(".Lloop{}:\n", label));
+            if map.iter().all(|&(k, _)| self.check(k)) {
+                self.add(0, d);
+                for &(k, v) in map {
+                    self.add(k, v);
+                }
+            }
+            self.line("cmpb $0, (%r12,%rbx)");
+            self.line(&format!("jne .Lloop{}", label));
+            self.out.push_str(&format!(".Lend{}:\n", label));
+            return;
+        };
+        if map.is_empty() {
+            self.line("movb $0, (%r12,%rbx)");
+            return;
+        }
+
+        let label = self.label();
+        self.line("movzbl (%r12,%rbx), %eax");
+        self.line("test %al, %al");
+        self.line(&format!("jz .Lend{}", label));
+        if map.iter().all(|&(k, _)| self.check(k)) {
+            if factor != 1 {
+                self.line(&format!("imul ${}, %eax, %eax", factor));
+            }
+            self.line("movb $0, (%r12,%rbx)");
+            for &(k, v) in map {
+                if v == 1 {
+                    self.line(&format!("add %al, {}", cell(k)));
+                } else {
+                    self.line(&format!("imul ${}, %eax, %edx", v));
+                    self.line(&format!("add %dl, {}", cell(k)));
+                }
+            }
+        }
+        self.out.push_str(&format!(".Lend{}:\n", label));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::emit_asm;
+
+    use crate::runner::{Config, Eof};
+    use crate::structs::Op::*;
+    use crate::structs::OpStream;
+
+    #[test]
+    fn test_emit_asm() {
+        let ops = [
+            Add(3),
+            Loop(OpStream {
+                ops: vec![Mov(1), Add(0xfe), Out, Mov(-1), Add(0xff)],
+            }),
+        ];
+        let config = Config {
+            tape_size: Some(100),
+            ..Config::default()
+        };
+        assert_eq!(
+            emit_asm(&ops, &config),
+            r#"# Generated by brain_rust.
+# %r12 holds the address of the tape and %rbx the pointer.
+
+	.set TAPE_SIZE, 100
+
+	.bss
+tape:
+	.zero TAPE_SIZE
+
+	.text
+	.globl _start
+_start:
+	lea tape(%rip), %r12
+	xor %ebx, %ebx
+	addb $3, (%r12,%rbx)
+	cmpb $0, (%r12,%rbx)
+	je .Lend1
+.Lloop1:
+	cmp $TAPE_SIZE-1, %rbx
+	jae off_tape
+	add $1, %rbx
+	subb $2, (%r12,%rbx)
+	call write_cell
+	cmp $1, %rbx
+	jb off_tape
+	sub $1, %rbx
+	decb (%r12,%rbx)
+	cmpb $0, (%r12,%rbx)
+	jne .Lloop1
+.Lend1:
+	mov $60, %eax
+	xor %edi, %edi
+	syscall
+
+# Writes the current cell.
+write_cell:
+	mov $1, %eax
+	mov $1, %edi
+	lea (%r12,%rbx), %rsi
+	mov $1, %edx
+	syscall
+	test %rax, %rax
+	js io_error
+	ret
+
+io_error:
+	mov $60, %eax
+	mov $1, %edi
+	syscall
+
+off_tape:
+	mov $1, %eax
+	mov $2, %edi
+	lea message(%rip), %rsi
+	mov $message_size, %edx
+	syscall
+	mov $60, %eax
+	mov $1, %edi
+	syscall
+
+	.section .rodata
+message:
+	.ascii "Pointer moved off the tape\n"
+	.set message_size, . - message
+
+	.section .note.GNU-stack,"",@progbits
+"#
+        );
+    }
+
+    #[test]
+    fn test_emit_asm_input_and_transfer() {
+        let ops = [
+            In,
+            Transfer(0xff, vec![(-1, 1), (2, 3)]),
+            Transfer(1, vec![]),
+            Transfer(0xff, vec![(1 << 40, 1)]),
+        ];
+        let config = Config {
+            eof: Eof::Zero,
+            ..Config::default()
+        };
+        let asm = emit_asm(&ops, &config);
+        assert!(asm.contains(concat!(
+            "\tcall read_cell\n",
+            "\tmovzbl (%r12,%rbx), %eax\n",
+            "\ttest %al, %al\n",
+            "\tjz .Lend1\n",
+            "\tcmp $1, %rbx\n",
+            "\tjb off_tape\n",
+            "\tcmp $TAPE_SIZE-2, %rbx\n",
+            "\tjae off_tape\n",
+            "\tmovb $0, (%r12,%rbx)\n",
+            "\tadd %al, -1(%r12,%rbx)\n",
+            "\timul $3, %eax, %edx\n",
+            "\tadd %dl, 2(%r12,%rbx)\n",
+            ".Lend1:\n",
+            "\tmovb $0, (%r12,%rbx)\n",
+            "\tmovzbl (%r12,%rbx), %eax\n",
+            "\ttest %al, %al\n",
+            "\tjz .Lend2\n",
+            "\tjmp off_tape\n",
+            ".Lend2:\n",
+        )));
+        assert!(asm.contains(".Leof:\n\tmovb $0, (%r12,%rbx)\n\tret\n"));
+    }
+}
@@ -0,0 +1,256 @@
+use std::fmt::{self, Display, Formatter};
+
+use crate::structs::Op;
+use crate::structs::Op::*;
+
+/// A single instruction of a [`Bytecode`] program.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Instr {
+    /// Like [`Op::Add`].
+    Add(u8),
+    /// Like [`Op::Mov`].
+    Mov(isize),
+    /// Like [`Op::In`].
+    In,
+    /// Like [`Op::Out`].
+    Out,
+    /// Start of a loop: continues at the given instruction, the one after the
+    /// end of the loop, if the current cell is zero.
+    JumpIfZero(usize),
+    /// End of a loop: continues at the given instruction, the first one of
+    /// the loop body, if the current cell is not zero.
+    JumpIfNotZero(usize),
+    /// Like [`Op::Transfer`].
+    Transfer(u8, Vec<(isize, u8)>),
+}
+
+impl Display for Instr {
+    /// Writes the instruction as an assembly-like mnemonic with its operands,
+    /// additions as signed numbers, e.g. `add -1` or `jz 7`.
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            Instr::Add(i) => write!(f, "add {}", i as i8),
+            Instr::Mov(n) => write!(f, "mov {}", n),
+            Instr::In => write!(f, "in"),
+            Instr::Out => write!(f, "out"),
+            Instr::JumpIfZero(target) => write!(f, "jz {}", target),
+            Instr::JumpIfNotZero(target) => write!(f, "jnz {}", target),
+            Instr::Transfer(d, ref map) => {
+                write!(f, "transfer {}", d as i8)?;
+                map.iter()
+                    .try_for_each(|&(k, v)| write!(f, ", [{}] += {}", k, v as i8))
+            }
+        }
+    }
+}
+
+/// A program compiled into a flat list of instructions, with loops turned into
+/// jumps, for [`State::run_bytecode`](crate::State::run_bytecode).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Bytecode {
+    code: Vec<Instr>,
+}
+
+impl Bytecode {
+    /// Compiles the operations, resolving the targets of all jumps.
+    pub fn compile(ops: &[Op]) -> Bytecode {
+        let mut bytecode = Bytecode { code: vec![] };
+        bytecode.emit(ops);
+        bytecode
+    }
+
+    /// The instructions in order.
+    pub fn instructions(&self) -> &[Instr] {
+        &self.code[..]
+    }
+
+    fn emit(&mut self, ops: &[Op]) {
+        for op in ops {
+            match *op {
+                Add(i) => self.code.push(Instr::Add(i)),
+                Mov(n) => self.code.push(Instr::Mov(n)),
+                In => self.code.push(Instr::In),
+                Out => self.code.push(Instr::Out),
+                Loop(ref stream) => {
+                    let start = self.code.len();
+                    self.code.push(Instr::JumpIfZero(0));
+                    self.emit(stream.get());
+                    self.code.push(Instr::JumpIfNotZero(start + 1));
+                    self.code[start] = Instr::JumpIfZero(self.code.len());
+                }
+                Transfer(d, ref map) => self.code.push(Instr::Transfer(d, map.clone())),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::{Bytecode, Instr};
+
+    use crate::runner::{Error, State};
+    use crate::strategies::opstream;
+    use crate::structs::Op::*;
+    use crate::structs::{Op, OpStream};
+
+    const MAX_STEPS: u64 = 10_000;
+
+    type Observation = (Vec<u8>, Vec<u8>, usize, Result<bool, Error>);
+
+    fn observe(ops: &[Op], input: &[u8], bytecode: bool) -> Observation {
+        observe_with(ops, input, bytecode, Some(MAX_STEPS))
+    }
+
+    fn observe_with(
+        ops: &[Op],
+        input: &[u8],
+        bytecode: bool,
+        max_steps: Option<u64>,
+    ) -> Observation {
+        let mut input = input;
+        let mut output = vec![];
+        let (memory, index, exit) = {
+            let mut state = State::new(&mut input, &mut output);
+            if let Some(max_steps) = max_steps {
+                state.set_max_steps(max_steps);
+            }
+            let exit = if bytecode {
+                state.run_bytecode(&Bytecode::compile(ops))
+            } else {
+                state.run(ops)
+            };
+            (state.memory().to_vec(), state.index(), exit)
+        };
+        (output, memory, index, exit)
+    }
+
+    proptest! {
+        #[test]
+        fn test_bytecode_keeps_behaviour(
+            mut opstream in opstream(true),
+            input in prop::collection::vec(any::<u8>(), 0..16),
+            optimize: bool,
+        ) {
+            if optimize {
+                opstream.optimize();
+            }
+            prop_assert_eq!(
+                observe(opstream.get(), &input, false),
+                observe(opstream.get(), &input, true)
+            );
+        }
+
+        #[test]
+        fn test_bytecode_unchecked_keeps_behaviour(
+            mut opstream in opstream(true),
+            input in prop::collection::vec(any::<u8>(), 0..16),
+            optimize: bool,
+        ) {
+            if optimize {
+                opstream.optimize();
+            }
+            let checked = observe(opstream.get(), &input, true);
+            // without a step budget, only programs that end can be run
+            prop_assume!(checked.3 != Err(Error::OutOfSteps));
+            prop_assert_eq!(checked, observe_with(opstream.get(), &input, true, None));
+        }
+    }
+
+    #[test]
+    fn test_bytecode_compile() {
+        let ops = [
+            Add(1),
+            Loop(OpStream {
+                ops: vec![Mov(1), Loop(OpStream { ops: vec![] }), In],
+            }),
+            Transfer(0xff, vec![(1, 2)]),
+            Out,
+        ];
+
+        assert_eq!(
+            Bytecode::compile(&ops).instructions(),
+            &[
+                Instr::Add(1),
+                Instr::JumpIfZero(7),
+                Instr::Mov(1),
+                Instr::JumpIfZero(5),
+                Instr::JumpIfNotZero(4),
+                Instr::In,
+                Instr::JumpIfNotZero(2),
+                Instr::Transfer(0xff, vec![(1, 2)]),
+                Instr::Out,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_instr_display() {
+        let code = [
+            Instr::Add(0xff),
+            Instr::Mov(2),
+            Instr::JumpIfZero(7),
+            Instr::JumpIfNotZero(3),
+            Instr::Transfer(0xff, vec![(-1, 2), (3, 0xfe)]),
+        ];
+        assert_eq!(
+            code.map(|instr| instr.to_string()),
+            [
+                "add -1",
+                "mov 2",
+                "jz 7",
+                "jnz 3",
+                "transfer -1, [-1] += 2, [3] += -2"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_bytecode_run_traced() {
+        let ops = [
+            Add(2),
+            Loop(OpStream {
+                ops: vec![Add(0xff)],
+            }),
+        ];
+        let mut input = &b""[..];
+        let mut output = vec![];
+        let mut state = State::new(&mut input, &mut output);
+        let mut trace = vec![];
+        let result = state.run_bytecode_traced(&Bytecode::compile(&ops), |pc, state| {
+            trace.push((pc, state.memory().first().copied().unwrap_or(0)))
+        });
+        assert_eq!(Ok(true), result);
+        assert_eq!(trace, [(0, 0), (1, 2), (2, 2), (3, 1), (2, 1), (3, 0)]);
+    }
+
+    #[test]
+    fn test_bytecode_run_non_termination() {
+        let ops = [Add(1), Loop(OpStream { ops: vec![] })];
+        let mut input = &b""[..];
+        let mut output = vec![];
+        let mut state = State::new(&mut input, &mut output);
+        assert_eq!(
+            Err(Error::NonTermination),
+            state.run_bytecode(&Bytecode::compile(&ops))
+        );
+    }
+
+    #[test]
+    fn test_bytecode_run() {
+        let ops = [
+            Add(3),
+            Loop(OpStream {
+                ops: vec![Mov(1), Add(2), Mov(-1), Add(0xff)],
+            }),
+            Mov(1),
+            Out,
+        ];
+        let mut input = &b""[..];
+        let mut output = vec![];
+        let mut state = State::new(&mut input, &mut output);
+        assert_eq!(Ok(true), state.run_bytecode(&Bytecode::compile(&ops)));
+        assert_eq!(vec![6u8], output);
+    }
+}
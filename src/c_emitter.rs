@@ -0,0 +1,286 @@
+use std::fmt::Write;
+
+use crate::optimizer::transfer_factor;
+use crate::runner::{Config, Eof};
+use crate::structs::Op::*;
+use crate::structs::{contains, Op};
+
+const TAPE_SIZE: usize = 30000;
+
+/// Emits a C program with the same behaviour as the operations. The tape has
+/// the configured size, 30000 cells by default, and the program stops with an
+/// error when the pointer leaves it.
+pub fn emit_c(ops: &[Op], config: &Config) -> String {
+    let mut emitter = Emitter {
+        out: String::new(),
+        depth: 1,
+    };
+    emitter.emit(ops);
+    let body = emitter.out;
+
+    let mut out = String::new();
+    out.push_str("#include <stddef.h>\n#include <stdio.h>\n#include <stdlib.h>\n\n");
+    writeln!(
+        out,
+        "#define TAPE_SIZE {}\n",
+        config.tape_size.unwrap_or(TAPE_SIZE)
+    )
+    .unwrap();
+    out.push_str("static unsigned char tape[TAPE_SIZE];\nstatic size_t p;\n");
+
+    if contains(ops, &|op| match op {
+        Mov(_) => true,
+        Transfer(_, map) => !map.is_empty(),
+        _ => false,
+    }) {
+        out.push_str(concat!(
+            "\n",
+            "/* The index of the cell at `offset` from the pointer. */\n",
+            "static size_t at(ptrdiff_t offset) {\n",
+            "    if (offset < 0 ? (size_t)-offset > p : (size_t)offset >= TAPE_SIZE - p) {\n",
+            "        fputs(\"Pointer moved off the tape\\n\", stderr);\n",
+            "        exit(1);\n",
+            "    }\n",
+            "    return p + offset;\n",
+            "}\n",
+        ));
+    }
+
+    if contains(ops, &|op| matches!(op, In)) {
+        let eof = match config.eof {
+            Eof::Stop => "exit(0);",
+            Eof::Unchanged => "return;",
+            Eof::Zero => "c = 0;",
+            Eof::MinusOne => "c = 255;",
+        };
+        write!(
+            out,
+            concat!(
+                "\n",
+                "static void input(void) {{\n",
+                "    int c = getchar();\n",
+                "    if (c == EOF) {{\n",
+                "        {}\n",
+                "    }}\n",
+                "    tape[p] = c;\n",
+                "}}\n",
+            ),
+            eof
+        )
+        .unwrap();
+    }
+
+    writeln!(out, "\nint main(void) {{\n{}    return 0;\n}}", body).unwrap();
+    out
+}
+
+fn cell(offset: isize) -> String {
+    match offset {
+        0 => "tape[p]".to_string(),
+        k if k < 0 => format!("tape[p - {}]", k.unsigned_abs()),
+        k => format!("tape[p + {}]", k),
+    }
+}
+
+struct Emitter {
+    out: String,
+    depth: usize,
+}
+
+impl Emitter {
+    fn line(&mut self, line: &str) {
+        for _ in 0..self.depth {
+            self.out.push_str("    ");
+        }
+        self.out.push_str(line);
+        self.out.push('\n');
+    }
+
+    fn add(&mut self, offset: isize, value: u8) {
+        let line = match value {
+            1 => format!("++{};", cell(offset)),
+            0xff => format!("--{};", cell(offset)),
+            v if v < 0x80 => format!("{} += {};", cell(offset), v),
+            v => format!("{} -= {};", cell(offset), v.wrapping_neg()),
+        };
+        self.line(&line);
+    }
+
+    fn emit(&mut self, ops: &[Op]) {
+        for op in ops {
+            match *op {
+                Add(i) => self.add(0, i),
+                Mov(n) => self.line(&format!("p = at({});", n)),
+                In => self.line("input();"),
+                Out => self.line("putchar(tape[p]);"),
+                Loop(ref stream) => {
+                    self.line("while (tape[p]) {");
+                    self.depth += 1;
+                    self.emit(stream.get());
+                    self.depth -= 1;
+                    self.line("}");
+                }
+                Transfer(d, ref map) => self.emit_transfer(d, map),
+            }
+        }
+    }
+
+    fn emit_transfer(&mut self, d: u8, map: &[(isize, u8)]) {
+        // Even steps may stall, so they stay loops.
+        let Some(factor) = transfer_factor(d) else {
+            self.line("while (tape[p]) {");
+            self.depth += 1;
+            for &(k, _) in map {
+                self.line(&format!("at({});", k));
+            }
+            self.add(0, d);
+            for &(k, v) in map {
+                self.add(k, v);
+            }
+            self.depth -= 1;
+            self.line("}");
+            return;
+        };
+        if map.is_empty() {
+            self.line("tape[p] = 0;");
+            return;
+        }
+
+        self.line("if (tape[p]) {");
+        self.depth += 1;
+        for &(k, _) in map {
+            self.line(&format!("at({});", k));
+        }
+        if factor == 1 {
+            self.line("unsigned char n = tape[p];");
+        } else {
+            self.line(&format!("unsigned char n = tape[p] * {};", factor));
+        }
+        self.line("tape[p] = 0;");
+        for &(k, v) in map {
+            if v == 1 {
+                self.line(&format!("{} += n;", cell(k)));
+            } else {
+                self.line(&format!("{} += n * {};", cell(k), v));
+            }
+        }
+        self.depth -= 1;
+        self.line("}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::emit_c;
+
+    use crate::runner::{Config, Eof};
+    use crate::structs::Op::*;
+    use crate::structs::OpStream;
+
+    #[test]
+    fn test_emit_c() {
+        let ops = [
+            Add(3),
+            Loop(OpStream {
+                ops: vec![Mov(1), Add(0xfe), Out, Mov(-1), Add(0xff)],
+            }),
+        ];
+        let config = Config {
+            tape_size: Some(100),
+            ..Config::default()
+        };
+        assert_eq!(
+            emit_c(&ops, &config),
+            r#"#include <stddef.h>
+#include <stdio.h>
+#include <stdlib.h>
+
+#define TAPE_SIZE 100
+
+static unsigned char tape[TAPE_SIZE];
+static size_t p;
+
+/* The index of the cell at `offset` from the pointer. */
+static size_t at(ptrdiff_t offset) {
+    if (offset < 0 ? (size_t)-offset > p : (size_t)offset >= TAPE_SIZE - p) {
+        fputs("Pointer moved off the tape\n", stderr);
+        exit(1);
+    }
+    return p + offset;
+}
+
+int main(void) {
+    tape[p] += 3;
+    while (tape[p]) {
+        p = at(1);
+        tape[p] -= 2;
+        putchar(tape[p]);
+        p = at(-1);
+        --tape[p];
+    }
+    return 0;
+}
+"#
+        );
+    }
+
+    #[test]
+    fn test_emit_c_input_and_transfer() {
+        let ops = [
+            In,
+            Transfer(0xff, vec![(-1, 1), (2, 3)]),
+            Transfer(1, vec![]),
+            Transfer(2, vec![]),
+        ];
+        let config = Config {
+            eof: Eof::Zero,
+            ..Config::default()
+        };
+        assert_eq!(
+            emit_c(&ops, &config),
+            r#"#include <stddef.h>
+#include <stdio.h>
+#include <stdlib.h>
+
+#define TAPE_SIZE 30000
+
+static unsigned char tape[TAPE_SIZE];
+static size_t p;
+
+/* The index of the cell at `offset` from the pointer. */
+static size_t at(ptrdiff_t offset) {
+    if (offset < 0 ? (size_t)-offset > p : (size_t)offset >= TAPE_SIZE - p) {
+        fputs("Pointer moved off the tape\n", stderr);
+        exit(1);
+    }
+    return p + offset;
+}
+
+static void input(void) {
+    int c = getchar();
+    if (c == EOF) {
+        c = 0;
+    }
+    tape[p] = c;
+}
+
+int main(void) {
+    input();
+    if (tape[p]) {
+        at(-1);
+        at(2);
+        unsigned char n = tape[p];
+        tape[p] = 0;
+        tape[p - 1] += n;
+        tape[p + 2] += n * 3;
+    }
+    tape[p] = 0;
+    while (tape[p]) {
+        tape[p] += 2;
+    }
+    return 0;
+}
+"#
+        );
+    }
+}
@@ -0,0 +1,526 @@
+#![cfg(feature = "cranelift")]
+use std::io::{self, Read, Write};
+use std::mem;
+use std::slice::from_mut;
+use std::time::{Duration, Instant};
+
+use cranelift::codegen::ir::FuncRef;
+use cranelift::codegen::Context;
+use cranelift::jit::{JITBuilder, JITModule};
+use cranelift::module::{default_libcall_names, Linkage, Module};
+use cranelift::prelude::*;
+
+use crate::optimizer::transfer_factor;
+use crate::output::{Flush, OutputBuffer};
+use crate::runner::{finish, Eof, Error};
+use crate::structs::Op;
+use crate::structs::Op::*;
+
+const TAPE_SIZE: usize = 30000;
+
+/// Compiles programs with Cranelift and runs them on a fixed tape of 30000
+/// cells unless configured otherwise.
+pub struct CraneliftState<'a, R: Read, W: Write> {
+    index: usize,
+    memory: Vec<u8>,
+    input: &'a mut R,
+    output: OutputBuffer<'a, W>,
+    optimize: bool,
+    eof: bool,
+    on_eof: Eof,
+    compile_time: Duration,
+    out_of_range: bool,
+    io_error: Option<Error>,
+}
+
+struct Compiler<'a> {
+    builder: FunctionBuilder<'a>,
+
+    memory: Value,
+    state: Value,
+    ptr: Variable,
+    tape_size: usize,
+
+    exit_block: Block,
+    out_of_range_block: Block,
+
+    getcharfn: FuncRef,
+    putcharfn: FuncRef,
+}
+
+impl<'a> Compiler<'a> {
+    fn cell_addr(&mut self, offset: isize) -> Value {
+        let ptr = self.builder.use_var(self.ptr);
+        let ptr = self.builder.ins().iadd_imm(ptr, offset as i64);
+        self.builder.ins().iadd(self.memory, ptr)
+    }
+
+    fn load(&mut self, addr: Value) -> Value {
+        self.builder
+            .ins()
+            .load(types::I8, MemFlags::trusted(), addr, 0)
+    }
+
+    fn store(&mut self, addr: Value, value: Value) {
+        self.builder
+            .ins()
+            .store(MemFlags::trusted(), value, addr, 0);
+    }
+
+    /// Leaves the function with the pointer unchanged unless cell `offset` is
+    /// on the tape.
+    fn check_offset(&mut self, offset: isize) {
+        let ptr = self.builder.use_var(self.ptr);
+        let target = self.builder.ins().iadd_imm(ptr, offset as i64);
+        let outside = self.builder.ins().icmp_imm(
+            IntCC::UnsignedGreaterThanOrEqual,
+            target,
+            self.tape_size as i64,
+        );
+        let next_block = self.builder.create_block();
+        self.builder
+            .ins()
+            .brif(outside, self.out_of_range_block, &[ptr], next_block, &[]);
+        self.builder.switch_to_block(next_block);
+    }
+
+    fn compile_add(&mut self, offset: isize, i: Value) {
+        let addr = self.cell_addr(offset);
+        let v = self.load(addr);
+        let v = self.builder.ins().iadd(v, i);
+        self.store(addr, v);
+    }
+
+    fn compile_mov(&mut self, n: isize) {
+        self.check_offset(n);
+        let ptr = self.builder.use_var(self.ptr);
+        let ptr = self.builder.ins().iadd_imm(ptr, n as i64);
+        self.builder.def_var(self.ptr, ptr);
+    }
+
+    fn compile_in(&mut self) {
+        self.compile_call(self.getcharfn);
+    }
+
+    fn compile_out(&mut self) {
+        self.compile_call(self.putcharfn);
+    }
+
+    /// Calls `getchar` or `putchar` on the current cell and leaves the
+    /// program if it returns false.
+    fn compile_call(&mut self, function: FuncRef) {
+        let addr = self.cell_addr(0);
+        let call = self.builder.ins().call(function, &[addr, self.state]);
+        let proceed = self.builder.inst_results(call)[0];
+        let ptr = self.builder.use_var(self.ptr);
+        let next_block = self.builder.create_block();
+        self.builder
+            .ins()
+            .brif(proceed, next_block, &[], self.exit_block, &[ptr]);
+        self.builder.switch_to_block(next_block);
+    }
+
+    /// Emits a loop around the body emitted by `body`, which runs while the
+    /// current cell is not zero.
+    fn compile_while(&mut self, body: impl FnOnce(&mut Self)) {
+        let test_block = self.builder.create_block();
+        let body_block = self.builder.create_block();
+        let next_block = self.builder.create_block();
+
+        self.builder.ins().jump(test_block, &[]);
+        self.builder.switch_to_block(test_block);
+        let addr = self.cell_addr(0);
+        let v = self.load(addr);
+        self.builder.ins().brif(v, body_block, &[], next_block, &[]);
+
+        self.builder.switch_to_block(body_block);
+        body(self);
+        self.builder.ins().jump(test_block, &[]);
+
+        self.builder.switch_to_block(next_block);
+    }
+
+    fn compile_transfer(&mut self, d: u8, map: &[(isize, u8)]) {
+        // Even steps may stall, so they stay loops.
+        let Some(factor) = transfer_factor(d) else {
+            self.compile_while(|compiler| {
+                for &(k, _) in map {
+                    compiler.check_offset(k);
+                }
+                let d = compiler.builder.ins().iconst(types::I8, d as i64);
+                compiler.compile_add(0, d);
+                for &(k, v) in map {
+                    let v = compiler.builder.ins().iconst(types::I8, v as i64);
+                    compiler.compile_add(k, v);
+                }
+            });
+            return;
+        };
+
+        let body_block = self.builder.create_block();
+        let next_block = self.builder.create_block();
+        let addr = self.cell_addr(0);
+        let v0 = self.load(addr);
+        self.builder
+            .ins()
+            .brif(v0, body_block, &[], next_block, &[]);
+
+        self.builder.switch_to_block(body_block);
+        for &(k, _) in map {
+            self.check_offset(k);
+        }
+        let iterations = self.builder.ins().imul_imm(v0, factor as i64);
+        let zero = self.builder.ins().iconst(types::I8, 0);
+        let addr = self.cell_addr(0);
+        self.store(addr, zero);
+        for &(k, v) in map {
+            let v = self.builder.ins().imul_imm(iterations, v as i64);
+            self.compile_add(k, v);
+        }
+        self.builder.ins().jump(next_block, &[]);
+
+        self.builder.switch_to_block(next_block);
+    }
+
+    fn compile(&mut self, ops: &[Op]) {
+        for op in ops {
+            match *op {
+                Add(i) => {
+                    let i = self.builder.ins().iconst(types::I8, i as i64);
+                    self.compile_add(0, i);
+                }
+                Mov(n) => self.compile_mov(n),
+                In => self.compile_in(),
+                Out => self.compile_out(),
+                Loop(ref stream) => self.compile_while(|compiler| compiler.compile(stream.get())),
+                Transfer(d, ref map) => self.compile_transfer(d, map),
+            }
+        }
+    }
+}
+
+impl<'a, R: Read, W: Write> CraneliftState<'a, R, W> {
+    /// Creates a JIT with an empty tape. `optimize` makes Cranelift optimize
+    /// for speed.
+    pub fn new<'b>(
+        input: &'b mut R,
+        output: &'b mut W,
+        optimize: bool,
+    ) -> CraneliftState<'b, R, W> {
+        CraneliftState {
+            index: 0,
+            memory: vec![0; TAPE_SIZE],
+            input,
+            output: OutputBuffer::new(output),
+            optimize,
+            eof: false,
+            on_eof: Eof::Stop,
+            compile_time: Duration::ZERO,
+            out_of_range: false,
+            io_error: None,
+        }
+    }
+
+    /// Resizes the tape to cells `0..tape_size`.
+    ///
+    /// # Panics
+    ///
+    /// If `tape_size` is 0, as the compiled code accesses cell 0 unchecked.
+    pub fn set_tape_size(&mut self, tape_size: usize) {
+        assert!(tape_size > 0, "The tape needs at least one cell");
+        self.memory.resize(tape_size, 0);
+    }
+
+    /// Sets what reading does at the end of the input, by default stopping
+    /// the program.
+    pub fn set_eof(&mut self, eof: Eof) {
+        self.on_eof = eof;
+    }
+
+    /// Sets when buffered output is written, by default after every line
+    /// break. It is always written before a run returns.
+    pub fn set_flush(&mut self, flush: Flush) {
+        self.output.flush = flush;
+    }
+
+    /// How long compiling took in the last run.
+    pub fn compile_time(&self) -> Duration {
+        self.compile_time
+    }
+
+    /// The position of the pointer.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// The whole tape.
+    pub fn memory(&self) -> &[u8] {
+        &self.memory[..]
+    }
+
+    /// Compiles and runs the operations on the current tape. Returns
+    /// `Ok(false)` if the program stopped at the end of the input.
+    pub fn run(&mut self, ops: &[Op]) -> Result<bool, Error> {
+        let start = Instant::now();
+        let mut flag_builder = settings::builder();
+        flag_builder
+            .set("opt_level", if self.optimize { "speed" } else { "none" })
+            .unwrap();
+        let isa = cranelift::native::builder()
+            .unwrap()
+            .finish(settings::Flags::new(flag_builder))
+            .unwrap();
+        let mut jit_builder = JITBuilder::with_isa(isa, default_libcall_names());
+        jit_builder.symbol("getchar", Self::getchar as *const u8);
+        jit_builder.symbol("putchar", Self::putchar as *const u8);
+        jit_builder.symbol("out_of_range", Self::out_of_range as *const u8);
+        let mut module = JITModule::new(jit_builder);
+
+        let size_t = module.target_config().pointer_type();
+        let call_conv = module.target_config().default_call_conv;
+        let signature = |params: usize, returns: &[Type]| {
+            let mut signature = Signature::new(call_conv);
+            signature.params = vec![AbiParam::new(size_t); params];
+            signature.returns = returns.iter().map(|&t| AbiParam::new(t)).collect();
+            signature
+        };
+        let getchar_id = module
+            .declare_function("getchar", Linkage::Import, &signature(2, &[types::I8]))
+            .unwrap();
+        let putchar_id = module
+            .declare_function("putchar", Linkage::Import, &signature(2, &[types::I8]))
+            .unwrap();
+        let out_of_range_id = module
+            .declare_function("out_of_range", Linkage::Import, &signature(1, &[]))
+            .unwrap();
+        let run_signature = signature(3, &[size_t]);
+        let run_id = module
+            .declare_function("run", Linkage::Export, &run_signature)
+            .unwrap();
+
+        let mut context = Context::new();
+        context.func.signature = run_signature;
+        let mut builder_context = FunctionBuilderContext::new();
+        let mut builder = FunctionBuilder::new(&mut context.func, &mut builder_context);
+
+        let entry_block = builder.create_block();
+        builder.append_block_params_for_function_params(entry_block);
+        let exit_block = builder.create_block();
+        builder.append_block_param(exit_block, size_t);
+        let out_of_range_block = builder.create_block();
+        builder.append_block_param(out_of_range_block, size_t);
+        builder.switch_to_block(entry_block);
+        let params = builder.block_params(entry_block).to_vec();
+
+        let ptr = Variable::new(0);
+        builder.declare_var(ptr, size_t);
+        builder.def_var(ptr, params[2]);
+
+        let getcharfn = module.declare_func_in_func(getchar_id, builder.func);
+        let putcharfn = module.declare_func_in_func(putchar_id, builder.func);
+        let out_of_rangefn = module.declare_func_in_func(out_of_range_id, builder.func);
+
+        let mut compiler = Compiler {
+            builder,
+
+            memory: params[0],
+            state: params[1],
+            ptr,
+            tape_size: self.memory.len(),
+
+            exit_block,
+            out_of_range_block,
+
+            getcharfn,
+            putcharfn,
+        };
+        compiler.compile(ops);
+
+        let mut builder = compiler.builder;
+        let end_ptr = builder.use_var(ptr);
+        builder.ins().jump(exit_block, &[end_ptr]);
+
+        builder.switch_to_block(out_of_range_block);
+        builder.ins().call(out_of_rangefn, &[params[1]]);
+        let ptr = builder.block_params(out_of_range_block)[0];
+        builder.ins().jump(exit_block, &[ptr]);
+
+        builder.switch_to_block(exit_block);
+        let ptr = builder.block_params(exit_block)[0];
+        builder.ins().return_(&[ptr]);
+
+        builder.seal_all_blocks();
+        builder.finalize();
+
+        module.define_function(run_id, &mut context).unwrap();
+        module.finalize_definitions().unwrap();
+        let function = unsafe {
+            mem::transmute::<*const u8, unsafe extern "C" fn(*mut u8, *mut Self, usize) -> usize>(
+                module.get_finalized_function(run_id),
+            )
+        };
+        self.compile_time = start.elapsed();
+
+        self.eof = false;
+        self.out_of_range = false;
+        self.io_error = None;
+        let memory = self.memory.as_mut_ptr();
+        let state = self as *mut Self;
+        self.index = unsafe { function(memory, state, (*state).index) };
+        unsafe { module.free_memory() };
+
+        let result = if self.out_of_range {
+            Err(Error::PointerOutOfRange)
+        } else if let Some(error) = self.io_error {
+            Err(error)
+        } else {
+            Ok(!self.eof)
+        };
+        finish(result, self.output.flush())
+    }
+
+    /// Notes an error writing the output, which stops the program.
+    fn output_result(&mut self, result: io::Result<()>) -> bool {
+        if let Err(e) = result {
+            self.io_error = Some(Error::Output(e.kind()));
+        }
+        self.io_error.is_none()
+    }
+
+    extern "C" fn getchar(ch: &mut u8, state: &mut CraneliftState<R, W>) -> bool {
+        let result = state.output.before_input();
+        if !state.output_result(result) {
+            return false;
+        }
+        // an error reading the input stops the program like its end, but is
+        // reported by `run`
+        let proceed = match state.input.read(from_mut(ch)) {
+            Ok(0) => state.on_eof.apply(ch),
+            Ok(_) => true,
+            Err(e) => {
+                state.io_error = Some(Error::Input(e.kind()));
+                return false;
+            }
+        };
+        state.eof = !proceed;
+        proceed
+    }
+
+    extern "C" fn putchar(ch: &u8, state: &mut CraneliftState<R, W>) -> bool {
+        let result = state.output.put(*ch);
+        state.output_result(result)
+    }
+
+    extern "C" fn out_of_range(state: &mut CraneliftState<R, W>) {
+        state.out_of_range = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::ErrorKind;
+
+    use proptest::prelude::*;
+
+    use super::{CraneliftState, TAPE_SIZE};
+
+    use crate::runner::{Config, Eof, Error, State};
+    use crate::strategies::opstream;
+    use crate::structs::Op::*;
+    use crate::structs::OpStream;
+
+    const CONFIG: Config = Config {
+        max_steps: Some(10_000),
+        tape_size: Some(TAPE_SIZE),
+        eof: Eof::Stop,
+    };
+
+    proptest! {
+        #[test]
+        fn test_cranelift_keeps_behaviour(
+            mut opstream in opstream(true),
+            input in prop::collection::vec(any::<u8>(), 0..16),
+            optimize: bool,
+        ) {
+            if optimize {
+                opstream.optimize();
+            }
+
+            let mut expected_input = &input[..];
+            let mut expected_output = vec![];
+            let mut state = State::with_config(&mut expected_input, &mut expected_output, &CONFIG);
+            let expected_exit = state.run(opstream.get());
+            // the JIT has no step budget
+            prop_assume!(expected_exit != Err(Error::OutOfSteps));
+            let mut expected_memory = state.memory().to_vec();
+            expected_memory.resize(TAPE_SIZE, 0);
+            let expected_index = state.index();
+
+            let mut actual_input = &input[..];
+            let mut actual_output = vec![];
+            let mut jit = CraneliftState::new(&mut actual_input, &mut actual_output, optimize);
+            let actual_exit = jit.run(opstream.get());
+            prop_assert_eq!(expected_exit, actual_exit);
+            prop_assert_eq!(expected_memory, jit.memory());
+            prop_assert_eq!(expected_index, jit.index());
+            prop_assert_eq!(expected_output, actual_output);
+        }
+    }
+
+    #[test]
+    fn test_cranelift_run() {
+        let mut input = &b"ab"[..];
+        let mut output = vec![];
+        let mut jit = CraneliftState::new(&mut input, &mut output, true);
+        let ops = [
+            In,
+            Mov(1),
+            Add(3),
+            Transfer(0xff, vec![(-1, 2)]),
+            Mov(-1),
+            Out,
+            In,
+            Out,
+            In,
+            Out,
+        ];
+        assert_eq!(Ok(false), jit.run(&ops));
+        assert_eq!(0, jit.index());
+        assert_eq!(vec![0x61 + 6, 0x62], output);
+    }
+
+    #[test]
+    fn test_cranelift_run_transfer_even_step() {
+        let mut input = &b""[..];
+        let mut output = vec![];
+        let mut jit = CraneliftState::new(&mut input, &mut output, false);
+        let ops = [Add(6), Transfer(0xfe, vec![(1, 1)]), Mov(1), Out];
+        assert_eq!(Ok(true), jit.run(&ops));
+        assert_eq!(vec![3], output);
+    }
+
+    #[test]
+    fn test_cranelift_run_out_of_range() {
+        let mut input = &b""[..];
+        let mut output = vec![];
+        let mut jit = CraneliftState::new(&mut input, &mut output, true);
+        jit.set_tape_size(4);
+        assert_eq!(Ok(true), jit.run(&[Mov(3), Add(1)]));
+        assert_eq!(Err(Error::PointerOutOfRange), jit.run(&[Mov(1)]));
+        assert_eq!(3, jit.index());
+        let transfer = Loop(OpStream {
+            ops: vec![Transfer(0xff, vec![(-4, 1)])],
+        });
+        assert_eq!(Err(Error::PointerOutOfRange), jit.run(&[transfer]));
+        assert_eq!(vec![0, 0, 0, 1], jit.memory());
+    }
+
+    #[test]
+    fn test_cranelift_run_output_error() {
+        let mut input = &b""[..];
+        let mut output = &mut [0u8; 0][..];
+        let mut jit = CraneliftState::new(&mut input, &mut output, true);
+        let program = [Add(1), Loop(OpStream { ops: vec![Out] })];
+        assert_eq!(Err(Error::Output(ErrorKind::WriteZero)), jit.run(&program));
+    }
+}
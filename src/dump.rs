@@ -0,0 +1,119 @@
+use std::fmt::Write;
+
+/// The cells from the first to the last non-zero one, or `None` if all are
+/// zero.
+fn non_zero_range(memory: &[u8]) -> Option<(usize, &[u8])> {
+    let start = memory.iter().position(|&cell| cell != 0)?;
+    let end = memory.iter().rposition(|&cell| cell != 0)? + 1;
+    Some((start, &memory[start..end]))
+}
+
+/// Shows the pointer position and the non-zero part of the tape as a table of
+/// the cells in hex, decimal and ASCII, with the cell under the pointer
+/// marked.
+pub fn dump_tape(memory: &[u8], index: usize) -> String {
+    let mut text = format!("pointer at cell {}\n", index);
+    let Some((start, cells)) = non_zero_range(memory) else {
+        text.push_str("all cells are zero\n");
+        return text;
+    };
+    text.push_str("    cell  hex  dec  ascii\n");
+    for (i, &cell) in cells.iter().enumerate() {
+        let ascii = match cell {
+            0x20..0x7f => cell as char,
+            _ => '.',
+        };
+        let marker = if start + i == index { '>' } else { ' ' };
+        writeln!(
+            text,
+            "{}{:7}   {:02x}  {:3}  {}",
+            marker,
+            start + i,
+            cell,
+            cell,
+            ascii
+        )
+        .unwrap();
+    }
+    text
+}
+
+/// Like [`dump_tape`], but as a line of JSON: an object with the `pointer`
+/// position, the position of the first non-zero cell as `start` and the
+/// non-zero range of `cells` as an array of numbers.
+pub fn dump_tape_json(memory: &[u8], index: usize) -> String {
+    let (start, cells) = non_zero_range(memory).unwrap_or((0, &[]));
+    let cells: Vec<String> = cells.iter().map(|cell| cell.to_string()).collect();
+    format!(
+        "{{\"pointer\": {}, \"start\": {}, \"cells\": [{}]}}\n",
+        index,
+        start,
+        cells.join(", ")
+    )
+}
+
+/// Shows the cells within `radius` of the pointer on one line, starting with
+/// the position of the first one and with the current cell in brackets, e.g.
+/// `pointer at 2, cells 0..5: 1 0 [3] 0 0`.
+pub fn dump_cells_around(memory: &[u8], index: usize, radius: usize) -> String {
+    let start = index.saturating_sub(radius);
+    let end = index.saturating_add(radius).saturating_add(1);
+    let cells: Vec<String> = (start..end)
+        .map(|i| {
+            let cell = memory.get(i).copied().unwrap_or(0);
+            if i == index {
+                format!("[{}]", cell)
+            } else {
+                cell.to_string()
+            }
+        })
+        .collect();
+    format!(
+        "pointer at {}, cells {}..{}: {}",
+        index,
+        start,
+        end,
+        cells.join(" ")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{dump_cells_around, dump_tape, dump_tape_json};
+
+    #[test]
+    fn test_dump_tape() {
+        assert_eq!(
+            dump_tape(&[0, 0x48, 0, 0xff, 0, 0], 3),
+            "pointer at cell 3\n    cell  hex  dec  ascii\n       1   48   72  H\n       2   00    0  .\n>      3   ff  255  .\n"
+        );
+        assert_eq!(
+            dump_tape(&[0, 0], 7),
+            "pointer at cell 7\nall cells are zero\n"
+        );
+    }
+
+    #[test]
+    fn test_dump_tape_json() {
+        assert_eq!(
+            dump_tape_json(&[0, 0x48, 0, 0xff, 0, 0], 3),
+            "{\"pointer\": 3, \"start\": 1, \"cells\": [72, 0, 255]}\n"
+        );
+        assert_eq!(
+            dump_tape_json(&[], 0),
+            "{\"pointer\": 0, \"start\": 0, \"cells\": []}\n"
+        );
+    }
+
+    #[test]
+    fn test_dump_cells_around() {
+        assert_eq!(
+            dump_cells_around(&[1, 0, 3], 2, 2),
+            "pointer at 2, cells 0..5: 1 0 [3] 0 0"
+        );
+        assert_eq!(
+            dump_cells_around(&[1, 2, 3, 4, 5, 6], 4, 1),
+            "pointer at 4, cells 3..6: 4 [5] 6"
+        );
+    }
+}
@@ -0,0 +1,264 @@
+use crate::parser::Node;
+
+/// Settings for [`format_source`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FormatConfig {
+    /// Spaces per level of loop nesting.
+    pub indent: usize,
+    /// Lines of commands are wrapped at this many columns. Comments are never
+    /// wrapped.
+    pub width: usize,
+}
+
+impl Default for FormatConfig {
+    fn default() -> FormatConfig {
+        FormatConfig {
+            indent: 4,
+            width: 80,
+        }
+    }
+}
+
+/// Lays out source parsed by [`parse_lossless`](crate::parse_lossless)
+/// without changing its commands or comments:
+///
+/// - Commands fill lines up to the configured width, broken between runs of
+///   the same command where possible.
+/// - Loops without comments or other loops in them stay on the line if they
+///   fit. Others put their brackets on lines of their own and indent their
+///   contents.
+/// - A comment on the same line after commands or a bracket stays there,
+///   other comment lines get lines of their own, and blank lines are kept as
+///   single ones. All other whitespace is dropped.
+pub fn format_source(nodes: &[Node], config: &FormatConfig) -> Vec<u8> {
+    let mut formatter = Formatter {
+        config,
+        out: vec![],
+        line: vec![],
+        line_depth: 0,
+        depth: 0,
+        closed: false,
+        blank: false,
+    };
+    formatter.format(nodes);
+    formatter.end_line();
+    formatter.out
+}
+
+struct Formatter<'a> {
+    config: &'a FormatConfig,
+    out: Vec<u8>,
+    /// The current line without its indentation.
+    line: Vec<u8>,
+    line_depth: usize,
+    depth: usize,
+    /// Whether the line only takes a comment anymore.
+    closed: bool,
+    /// Whether a blank line goes before the next one.
+    blank: bool,
+}
+
+impl Formatter<'_> {
+    fn end_line(&mut self) {
+        if self.line.is_empty() {
+            return;
+        }
+        if self.blank && !self.out.is_empty() {
+            self.out.push(b'\n');
+        }
+        self.blank = false;
+        let indent = self.line_depth * self.config.indent;
+        self.out.extend(std::iter::repeat_n(b' ', indent));
+        self.out.append(&mut self.line);
+        self.out.push(b'\n');
+        self.closed = false;
+    }
+
+    /// The number of columns left on the current line.
+    fn space(&self) -> usize {
+        let depth = if self.line.is_empty() {
+            self.depth
+        } else {
+            self.line_depth
+        };
+        let used = depth * self.config.indent + self.line.len();
+        self.config.width.saturating_sub(used)
+    }
+
+    /// Appends to the line, starting a new one first unless `text` fits.
+    fn push(&mut self, text: &[u8]) {
+        if self.closed || (!self.line.is_empty() && text.len() > self.space()) {
+            self.end_line();
+        }
+        if self.line.is_empty() {
+            self.line_depth = self.depth;
+        }
+        self.line.extend(text);
+    }
+
+    fn format(&mut self, nodes: &[Node]) {
+        let mut commands = vec![];
+        for node in nodes {
+            match node {
+                Node::Command(c) => commands.push(*c),
+                Node::Trivia(trivia) if trivia.trim_ascii().is_empty() => {
+                    if trivia.iter().filter(|&&c| c == b'\n').count() > 1 {
+                        self.push_commands(&mut commands);
+                        self.end_line();
+                        self.blank = true;
+                    }
+                }
+                Node::Trivia(trivia) => {
+                    self.push_commands(&mut commands);
+                    self.push_comment(trivia);
+                }
+                Node::Loop(nodes) => {
+                    self.push_commands(&mut commands);
+                    self.push_loop(nodes);
+                }
+            }
+        }
+        self.push_commands(&mut commands);
+    }
+
+    /// Pushes the commands run by run, splitting runs that don't fit on a
+    /// line of their own.
+    fn push_commands(&mut self, commands: &mut Vec<u8>) {
+        for run in commands.chunk_by(|a, b| a == b) {
+            let mut run = run;
+            while !run.is_empty() {
+                if run.len() > self.space() && !self.line.is_empty() {
+                    self.end_line();
+                }
+                let length = run.len().min(self.space().max(1));
+                self.push(&run[..length]);
+                run = &run[length..];
+            }
+        }
+        commands.clear();
+    }
+
+    fn push_comment(&mut self, trivia: &[u8]) {
+        let mut lines = trivia.split(|&c| c == b'\n');
+        let first = lines.next().unwrap().trim_ascii();
+        if !first.is_empty() {
+            if self.line.is_empty() {
+                self.push(first);
+            } else {
+                self.line.push(b' ');
+                self.line.extend(first);
+            }
+            self.end_line();
+        }
+        let mut empty = 0;
+        for line in lines {
+            let line = line.trim_ascii();
+            if line.is_empty() {
+                empty += 1;
+                continue;
+            }
+            self.end_line();
+            if empty > 0 {
+                self.blank = true;
+            }
+            empty = 0;
+            self.push(line);
+            self.end_line();
+        }
+        if empty > 1 {
+            self.end_line();
+            self.blank = true;
+        }
+    }
+
+    fn push_loop(&mut self, nodes: &[Node]) {
+        if let Some(text) = inline(nodes) {
+            if text.len()
+                <= self
+                    .config
+                    .width
+                    .saturating_sub(self.depth * self.config.indent)
+            {
+                self.push(&text);
+                return;
+            }
+        }
+        self.end_line();
+        self.push(b"[");
+        self.closed = true;
+        self.depth += 1;
+        self.format(nodes);
+        self.end_line();
+        self.depth -= 1;
+        self.blank = false;
+        self.push(b"]");
+        self.closed = true;
+    }
+}
+
+/// The text of a loop without comments or loops in it.
+fn inline(nodes: &[Node]) -> Option<Vec<u8>> {
+    let mut text = vec![b'['];
+    for node in nodes {
+        match node {
+            Node::Command(c) => text.push(*c),
+            Node::Trivia(trivia) if trivia.trim_ascii().is_empty() => {}
+            _ => return None,
+        }
+    }
+    text.push(b']');
+    Some(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::{format_source, FormatConfig};
+
+    use crate::parser::{parse, parse_lossless};
+    use crate::strategies::source;
+
+    fn format(text: &[u8], config: &FormatConfig) -> Vec<u8> {
+        format_source(&parse_lossless(text).unwrap(), config)
+    }
+
+    fn words(text: &[u8]) -> Vec<&[u8]> {
+        text.split(|c| b"+-<>.,[] \n".contains(c))
+            .filter(|word| !word.is_empty())
+            .collect()
+    }
+
+    proptest! {
+        #[test]
+        fn test_format_source_keeps_program(text in source(), width in 1..40usize) {
+            let config = FormatConfig { indent: 2, width };
+            let formatted = format(text.as_bytes(), &config);
+            prop_assert_eq!(parse(text.as_bytes()), parse(&formatted));
+            prop_assert_eq!(words(text.as_bytes()), words(&formatted));
+            prop_assert_eq!(&formatted, &format(&formatted, &config));
+        }
+    }
+
+    #[test]
+    fn test_format_source() {
+        let text = b"A comment\n+++ +++[>+++\n+<-] set up\n\n\n[\n-[->+<]]\n\ndone";
+        let config = FormatConfig {
+            indent: 2,
+            width: 12,
+        };
+        assert_eq!(
+            String::from_utf8(format(text, &config)).unwrap(),
+            "A comment\n++++++\n[>++++<-] set up\n\n[\n  -[->+<]\n]\n\ndone\n"
+        );
+    }
+
+    #[test]
+    fn test_format_source_nested_comments() {
+        let text = b"[ loop\n  >[-]< inner\n  lines\n] end";
+        assert_eq!(
+            String::from_utf8(format(text, &FormatConfig::default())).unwrap(),
+            "[ loop\n    >[-]< inner\n    lines\n] end\n"
+        );
+    }
+}
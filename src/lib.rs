@@ -0,0 +1,65 @@
+//! A somewhat optimizing Brainfuck interpreter.
+//!
+//! [`parse`] turns source text into a list of [`Op`]s. Wrapped in an
+//! [`OpStream`], they can be optimized into fewer and more specialized
+//! operations and then run by the interpreter in [`State`], either directly or
+//! after compiling them to [`Bytecode`], or compiled and run by a JIT:
+//! `LlvmState` with the `llvm` feature or `CraneliftState` with the `cranelift`
+//! feature. [`emit_c`] and [`emit_rust`] translate them into C and Rust,
+//! [`emit_wat`] and [`emit_wasm`] into WebAssembly and [`emit_asm`] into x86-64
+//! assembly. [`Op`] and [`OpStream`] implement `Display`, which writes them
+//! back as Brainfuck. [`HexWriter`] and [`EscapeWriter`] show binary output
+//! readably, and [`dump_tape`] the tape after a run.
+//!
+//! ```
+//! use brain_rust::{parse, Config, OpStream, State};
+//!
+//! let mut opstream = OpStream { ops: parse(b"++++++++[>++++++++<-]>+.").unwrap() };
+//! opstream.optimize();
+//!
+//! let (mut input, mut output) = (&b""[..], vec![]);
+//! let config = Config { max_steps: Some(1000), ..Config::default() };
+//! let mut state = State::with_config(&mut input, &mut output, &config);
+//! assert_eq!(Ok(true), state.run(opstream.get()));
+//! assert_eq!(b"A", &output[..]);
+//! ```
+
+#![warn(missing_docs)]
+
+mod asm_emitter;
+mod bytecode;
+mod c_emitter;
+mod cranelift_runner;
+mod dump;
+mod formatter;
+mod llvm_runner;
+mod minifier;
+mod object_cache;
+mod optimizer;
+mod output;
+mod parser;
+mod printer;
+mod runner;
+mod rust_emitter;
+#[cfg(test)]
+mod strategies;
+mod structs;
+pub mod verify;
+mod wasm_emitter;
+
+pub use asm_emitter::emit_asm;
+pub use bytecode::{Bytecode, Instr};
+pub use c_emitter::emit_c;
+#[cfg(feature = "cranelift")]
+pub use cranelift_runner::CraneliftState;
+pub use dump::{dump_cells_around, dump_tape, dump_tape_json};
+pub use formatter::{format_source, FormatConfig};
+#[cfg(feature = "llvm")]
+pub use llvm_runner::LlvmState;
+pub use minifier::minify;
+pub use output::{EscapeWriter, Flush, HexWriter};
+pub use parser::{parse, parse_lossless, Node};
+pub use runner::{Config, Eof, Error, State};
+pub use rust_emitter::emit_rust;
+pub use structs::{Op, OpStream};
+pub use wasm_emitter::{emit_wasm, emit_wat};
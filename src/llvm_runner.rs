@@ -1,24 +1,61 @@
 #![cfg(feature = "llvm")]
 use inkwell::types::IntType;
-use std::io::{Read, Write};
-use std::slice::{from_mut, from_ref};
+use std::ffi::CStr;
+use std::io::{self, Read, Write};
+use std::mem;
+use std::path::PathBuf;
+use std::ptr;
+use std::slice::from_mut;
+use std::time::{Duration, Instant};
 
 use inkwell::builder::{Builder, BuilderError};
 use inkwell::context::Context;
+use inkwell::llvm_sys::core::LLVMCreateMemoryBufferWithMemoryRangeCopy;
+use inkwell::llvm_sys::error::{LLVMDisposeErrorMessage, LLVMErrorRef, LLVMGetErrorMessage};
+use inkwell::llvm_sys::orc2::lljit::{
+    LLVMOrcCreateLLJIT, LLVMOrcDisposeLLJIT, LLVMOrcLLJITAddObjectFile,
+    LLVMOrcLLJITGetGlobalPrefix, LLVMOrcLLJITGetMainJITDylib, LLVMOrcLLJITLookup,
+    LLVMOrcLLJITMangleAndIntern, LLVMOrcLLJITRef,
+};
+use inkwell::llvm_sys::orc2::LLVMJITSymbolGenericFlags::{
+    LLVMJITSymbolGenericFlagsCallable, LLVMJITSymbolGenericFlagsExported,
+};
+use inkwell::llvm_sys::orc2::{
+    LLVMJITEvaluatedSymbol, LLVMJITSymbolFlags, LLVMOrcAbsoluteSymbols, LLVMOrcCSymbolMapPair,
+    LLVMOrcCreateDynamicLibrarySearchGeneratorForProcess, LLVMOrcDisposeMaterializationUnit,
+    LLVMOrcJITDylibAddGenerator, LLVMOrcJITDylibDefine,
+};
+use inkwell::module::Module;
+use inkwell::support::get_llvm_version;
+use inkwell::targets::{
+    CodeModel, FileType, InitializationConfig, RelocMode, Target, TargetData, TargetMachine,
+};
 use inkwell::values::{FunctionValue, IntValue, PointerValue};
 use inkwell::IntPredicate;
 use inkwell::OptimizationLevel;
 
+use crate::object_cache::ObjectCache;
+use crate::output::{Flush, OutputBuffer};
+use crate::runner::{finish, Eof, Error};
 use crate::structs::Op::*;
 use crate::structs::{Op, OpStream};
 
 const MEMSIZE: usize = 30000;
 
+/// Compiles programs with LLVM and runs them on a fixed tape of 30000 cells.
+/// There are no bounds checks, and [`Transfer`](Op::Transfer) operations are not supported.
 pub struct LlvmState<'a, R: Read, W: Write> {
-    memory: [i8; MEMSIZE],
+    index: usize,
+    memory: [u8; MEMSIZE],
     input: &'a mut R,
-    output: &'a mut W,
+    output: OutputBuffer<'a, W>,
     optimize: bool,
+    eof: bool,
+    on_eof: Eof,
+    compile_time: Duration,
+    cache_dir: Option<PathBuf>,
+    cache_hit: bool,
+    io_error: Option<Error>,
 }
 
 struct Compiler<'ctx, 'a> {
@@ -61,14 +98,27 @@ impl<'ctx, 'a> Compiler<'ctx, 'a> {
     }
 
     fn compile_in(&self, ptr: IntValue<'ctx>) -> Result<IntValue<'_>, BuilderError> {
+        self.compile_call(self.getcharfn, ptr)
+    }
+
+    fn compile_out(&self, ptr: IntValue<'ctx>) -> Result<IntValue<'_>, BuilderError> {
+        self.compile_call(self.putcharfn, ptr)
+    }
+
+    /// Calls `getchar` or `putchar` on the current cell and returns from the
+    /// program if it returns false.
+    fn compile_call(
+        &self,
+        function: FunctionValue<'ctx>,
+        ptr: IntValue<'ctx>,
+    ) -> Result<IntValue<'_>, BuilderError> {
         let mem_ptr = unsafe {
             self.builder
                 .build_gep(self.byte, self.memory, &[ptr], "mem_ptr")?
         };
-        let result = self
-            .builder
-            .build_call(self.getcharfn, &[mem_ptr.into(), self.state.into()], "call")
-            .unwrap();
+        let result =
+            self.builder
+                .build_call(function, &[mem_ptr.into(), self.state.into()], "call")?;
         let exit_block = self.context.append_basic_block(self.function, "exit");
         let next_block = self.context.append_basic_block(self.function, "next");
         self.builder.build_conditional_branch(
@@ -76,27 +126,17 @@ impl<'ctx, 'a> Compiler<'ctx, 'a> {
                 IntPredicate::EQ,
                 result.try_as_basic_value().left().unwrap().into_int_value(),
                 self.context.bool_type().const_zero(),
-                "eof",
+                "stop",
             )?,
             exit_block,
             next_block,
         )?;
         self.builder.position_at_end(exit_block);
-        self.builder.build_return(None).unwrap();
+        self.builder.build_return(Some(&ptr))?;
         self.builder.position_at_end(next_block);
         Ok(ptr)
     }
 
-    fn compile_out(&self, ptr: IntValue<'ctx>) -> Result<IntValue<'_>, BuilderError> {
-        let mem_ptr = unsafe {
-            self.builder
-                .build_gep(self.byte, self.memory, &[ptr], "mem_ptr")?
-        };
-        self.builder
-            .build_call(self.putcharfn, &[mem_ptr.into(), self.state.into()], "call")?;
-        Ok(ptr)
-    }
-
     fn compile_loop(
         &self,
         ptr: IntValue<'ctx>,
@@ -157,108 +197,404 @@ impl<'ctx, 'a> Compiler<'ctx, 'a> {
     }
 }
 
+/// Builds a function `run` into `module` that takes the tape and the state, and
+/// calls the external functions `getchar` and `putchar` with them.
+fn build_program<'ctx>(
+    context: &'ctx Context,
+    module: &Module<'ctx>,
+    target_data: &TargetData,
+    ops: &[Op],
+) {
+    let builder = context.create_builder();
+
+    let size_t = context.ptr_sized_int_type(target_data, Default::default());
+    let getcharfn = module.add_function(
+        "getchar",
+        context.bool_type().fn_type(
+            &[
+                context.ptr_type(Default::default()).into(),
+                context.ptr_type(Default::default()).into(),
+            ],
+            false,
+        ),
+        None,
+    );
+    let putcharfn = module.add_function(
+        "putchar",
+        context.bool_type().fn_type(
+            &[
+                context.ptr_type(Default::default()).into(),
+                context.ptr_type(Default::default()).into(),
+            ],
+            false,
+        ),
+        None,
+    );
+
+    let function = module.add_function(
+        "run",
+        size_t.fn_type(
+            &[
+                context.ptr_type(Default::default()).into(),
+                context.ptr_type(Default::default()).into(),
+            ],
+            false,
+        ),
+        None,
+    );
+    let entry_block = context.append_basic_block(function, "entry");
+    builder.position_at_end(entry_block);
+
+    let compiler = Compiler {
+        context,
+        builder: &builder,
+        function,
+
+        memory: function.get_nth_param(0).unwrap().into_pointer_value(),
+        state: function.get_nth_param(1).unwrap().into_pointer_value(),
+
+        getcharfn,
+        putcharfn,
+
+        size_t,
+        byte: context.i8_type(),
+    };
+
+    let end_ptr = compiler.compile(ops, size_t.const_zero());
+    builder.build_return(Some(&end_ptr)).unwrap();
+
+    module.verify().unwrap();
+}
+
+/// Compiles the operations into an object file for the cache.
+fn compile_object(target_machine: &TargetMachine, ops: &[Op]) -> Vec<u8> {
+    let context = Context::create();
+    let module = context.create_module("program");
+    module.set_triple(&target_machine.get_triple());
+    module.set_data_layout(&target_machine.get_target_data().get_data_layout());
+    build_program(&context, &module, &target_machine.get_target_data(), ops);
+    target_machine
+        .write_to_memory_buffer(&module, FileType::Object)
+        .unwrap()
+        .as_slice()
+        .to_vec()
+}
+
 impl<'a, R: Read, W: Write> LlvmState<'a, R, W> {
+    /// Creates a JIT with an empty tape. `optimize` selects LLVM's most
+    /// aggressive optimization level instead of none.
     pub fn new<'b>(input: &'b mut R, output: &'b mut W, optimize: bool) -> LlvmState<'b, R, W> {
         LlvmState {
+            index: 0,
             memory: [0; MEMSIZE],
             input,
-            output,
+            output: OutputBuffer::new(output),
             optimize,
+            eof: false,
+            on_eof: Eof::Stop,
+            compile_time: Duration::ZERO,
+            cache_dir: None,
+            cache_hit: false,
+            io_error: None,
         }
     }
 
-    pub fn run(&mut self, ops: &[Op]) -> bool {
+    /// Sets what reading does at the end of the input, by default stopping
+    /// the program.
+    pub fn set_eof(&mut self, eof: Eof) {
+        self.on_eof = eof;
+    }
+
+    /// Sets when buffered output is written, by default after every line
+    /// break. It is always written before a run returns.
+    pub fn set_flush(&mut self, flush: Flush) {
+        self.output.flush = flush;
+    }
+
+    /// Keeps compiled programs in `dir`, so that running a program again with
+    /// the same settings skips compiling it.
+    pub fn set_cache_dir(&mut self, dir: impl Into<PathBuf>) {
+        self.cache_dir = Some(dir.into());
+    }
+
+    /// How long compiling, or loading from the cache, took in the last run.
+    pub fn compile_time(&self) -> Duration {
+        self.compile_time
+    }
+
+    /// The position of the pointer.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// The whole tape.
+    pub fn memory(&self) -> &[u8] {
+        &self.memory[..]
+    }
+
+    /// Compiles and runs the operations on the current tape, or takes the
+    /// compiled program from the cache if there is one. Returns `Ok(false)`
+    /// if the program stopped at the end of the input.
+    pub fn run(&mut self, ops: &[Op]) -> Result<bool, Error> {
+        let start = Instant::now();
+        self.cache_hit = false;
+        let cached = self
+            .cache_dir
+            .clone()
+            .and_then(|dir| self.cached_program(&ObjectCache::new(&dir), ops));
+
         let context = Context::create();
         let module = context.create_module("program");
-        let execution_engine = module
-            .create_jit_execution_engine(if self.optimize {
-                OptimizationLevel::Aggressive
-            } else {
-                OptimizationLevel::None
-            })
-            .unwrap();
-        let builder = context.create_builder();
-
-        let size_t =
-            context.ptr_sized_int_type(execution_engine.get_target_data(), Default::default());
-        let getcharfn = module.add_function(
-            "getchar",
-            context.bool_type().fn_type(
-                &[
-                    context.ptr_type(Default::default()).into(),
-                    context.ptr_type(Default::default()).into(),
-                ],
-                false,
-            ),
-            None,
-        );
-        let putcharfn = module.add_function(
-            "putchar",
-            context.void_type().fn_type(
-                &[
-                    context.ptr_type(Default::default()).into(),
-                    context.ptr_type(Default::default()).into(),
-                ],
-                false,
-            ),
-            None,
-        );
-
-        let function = module.add_function(
-            "run",
-            context.void_type().fn_type(
-                &[
-                    context.ptr_type(Default::default()).into(),
-                    context.ptr_type(Default::default()).into(),
-                ],
-                false,
-            ),
-            None,
-        );
-        let entry_block = context.append_basic_block(function, "entry");
-        builder.position_at_end(entry_block);
-
-        let compiler = Compiler {
-            context: &context,
-            builder: &builder,
-            function,
-
-            memory: function.get_nth_param(0).unwrap().into_pointer_value(),
-            state: function.get_nth_param(1).unwrap().into_pointer_value(),
-
-            getcharfn,
-            putcharfn,
-
-            size_t: context
-                .ptr_sized_int_type(execution_engine.get_target_data(), Default::default()),
-            byte: context.i8_type(),
+        let mut execution_engine = None;
+        let address = match &cached {
+            Some((_, address)) => *address,
+            None => {
+                let engine = module
+                    .create_jit_execution_engine(self.optimization_level())
+                    .unwrap();
+                build_program(&context, &module, engine.get_target_data(), ops);
+                for (name, address) in Self::callbacks() {
+                    let function = module.get_function(name.to_str().unwrap()).unwrap();
+                    engine.add_global_mapping(&function, address);
+                }
+                execution_engine
+                    .insert(engine)
+                    .get_function_address("run")
+                    .unwrap()
+            }
+        };
+        let function = unsafe {
+            mem::transmute::<usize, unsafe extern "C" fn(*mut [u8; MEMSIZE], *mut Self) -> usize>(
+                address,
+            )
         };
+        self.compile_time = start.elapsed();
 
-        compiler.compile(ops, size_t.const_zero());
-        builder.build_return(None).unwrap();
+        self.eof = false;
+        self.io_error = None;
+        let state = self as *mut Self;
+        self.index = unsafe { function(&mut (*state).memory, state) };
 
-        module.verify().unwrap();
+        let result = match self.io_error {
+            Some(error) => Err(error),
+            None => Ok(!self.eof),
+        };
+        finish(result, self.output.flush())
+    }
 
-        execution_engine.add_global_mapping(&getcharfn, LlvmState::<R, W>::getchar as usize);
-        execution_engine.add_global_mapping(&putcharfn, LlvmState::<R, W>::putchar as usize);
+    /// The functions the compiled program calls.
+    fn callbacks() -> [(&'static CStr, usize); 2] {
+        [
+            (c"getchar", Self::getchar as *const () as usize),
+            (c"putchar", Self::putchar as *const () as usize),
+        ]
+    }
 
-        unsafe {
-            execution_engine
-                .get_function::<unsafe extern "C" fn(*mut [i8; MEMSIZE], *mut std::ffi::c_void) -> u64>(
-                    "run",
-                )
-                .unwrap()
-                .call(&mut self.memory as *mut [i8; MEMSIZE], /* FIXME: evil */ std::mem::transmute(self));
-        };
+    fn optimization_level(&self) -> OptimizationLevel {
+        if self.optimize {
+            OptimizationLevel::Aggressive
+        } else {
+            OptimizationLevel::None
+        }
+    }
+
+    /// The program linked from the object file in the cache, compiled and
+    /// stored there first if it is missing or broken. Returns `None` if
+    /// linking fails, so that the program is compiled without the cache.
+    fn cached_program(&mut self, cache: &ObjectCache, ops: &[Op]) -> Option<(Jit, usize)> {
+        Target::initialize_native(&InitializationConfig::default()).ok()?;
+        let target_machine = self.target_machine()?;
+        let key = self.cache_key(&target_machine, ops);
+        if let Some(program) = cache.get(&key).and_then(|object| Self::link(&object).ok()) {
+            self.cache_hit = true;
+            return Some(program);
+        }
+        let object = compile_object(&target_machine, ops);
+        // failing to fill the cache only costs time in later runs
+        let _ = cache.put(&key, &object);
+        Self::link(&object).ok()
+    }
+
+    /// Links an object file and finds its `run` function.
+    fn link(object: &[u8]) -> Result<(Jit, usize), String> {
+        let jit = Jit::new(object, &Self::callbacks())?;
+        let address = jit.lookup(c"run")?;
+        Ok((jit, address))
+    }
 
-        true
+    /// Notes an error writing the output, which stops the program.
+    fn output_result(&mut self, result: io::Result<()>) -> bool {
+        if let Err(e) = result {
+            self.io_error = Some(Error::Output(e.kind()));
+        }
+        self.io_error.is_none()
+    }
+
+    /// A target machine for the host, like the execution engine's.
+    fn target_machine(&self) -> Option<TargetMachine> {
+        let triple = TargetMachine::get_default_triple();
+        Target::from_triple(&triple).ok()?.create_target_machine(
+            &triple,
+            &TargetMachine::get_host_cpu_name().to_string_lossy(),
+            &TargetMachine::get_host_cpu_features().to_string_lossy(),
+            self.optimization_level(),
+            RelocMode::PIC,
+            CodeModel::Default,
+        )
+    }
+
+    /// The cache key of a program: the program together with the settings
+    /// (optimization level, tape size and EOF mode) and the compiler and host
+    /// it was compiled with.
+    fn cache_key(&self, target_machine: &TargetMachine, ops: &[Op]) -> String {
+        let program: String = ops.iter().map(|op| op.to_string()).collect();
+        format!(
+            "brain_rust {} LLVM {:?} {} {} {} optimize {} tape {} eof {:?} program {}",
+            env!("CARGO_PKG_VERSION"),
+            get_llvm_version(),
+            target_machine.get_triple().as_str().to_string_lossy(),
+            target_machine.get_cpu().to_string_lossy(),
+            target_machine.get_feature_string().to_string_lossy(),
+            self.optimize,
+            MEMSIZE,
+            self.on_eof,
+            program
+        )
     }
 
     extern "C" fn getchar(ch: &mut u8, state: &mut LlvmState<R, W>) -> bool {
-        state.input.read(from_mut(ch)).unwrap() != 0
+        let result = state.output.before_input();
+        if !state.output_result(result) {
+            return false;
+        }
+        // an error reading the input stops the program like its end, but is
+        // reported by `run`
+        let proceed = match state.input.read(from_mut(ch)) {
+            Ok(0) => state.on_eof.apply(ch),
+            Ok(_) => true,
+            Err(e) => {
+                state.io_error = Some(Error::Input(e.kind()));
+                return false;
+            }
+        };
+        state.eof = !proceed;
+        proceed
+    }
+
+    extern "C" fn putchar(ch: &u8, state: &mut LlvmState<R, W>) -> bool {
+        let result = state.output.put(*ch);
+        state.output_result(result)
     }
+}
+
+/// An ORC JIT that has linked an object file. inkwell's execution engine can
+/// only run modules it compiled itself, so this uses the C API directly.
+struct Jit(LLVMOrcLLJITRef);
+
+impl Jit {
+    /// Links `object`, resolving the functions it calls from `symbols`.
+    fn new(object: &[u8], symbols: &[(&CStr, usize)]) -> Result<Jit, String> {
+        unsafe {
+            let mut jit = ptr::null_mut();
+            check(LLVMOrcCreateLLJIT(&mut jit, ptr::null_mut()))?;
+            let jit = Jit(jit);
+            let dylib = LLVMOrcLLJITGetMainJITDylib(jit.0);
+            // like the execution engine, also resolve the process's symbols,
+            // which LLVM may call for things like `memset`
+            let mut generator = ptr::null_mut();
+            check(LLVMOrcCreateDynamicLibrarySearchGeneratorForProcess(
+                &mut generator,
+                LLVMOrcLLJITGetGlobalPrefix(jit.0),
+                None,
+                ptr::null_mut(),
+            ))?;
+            LLVMOrcJITDylibAddGenerator(dylib, generator);
+
+            let mut symbols: Vec<_> = symbols
+                .iter()
+                .map(|&(name, address)| LLVMOrcCSymbolMapPair {
+                    Name: LLVMOrcLLJITMangleAndIntern(jit.0, name.as_ptr()),
+                    Sym: LLVMJITEvaluatedSymbol {
+                        Address: address as u64,
+                        Flags: LLVMJITSymbolFlags {
+                            GenericFlags: LLVMJITSymbolGenericFlagsExported as u8
+                                | LLVMJITSymbolGenericFlagsCallable as u8,
+                            TargetFlags: 0,
+                        },
+                    },
+                })
+                .collect();
+            let unit = LLVMOrcAbsoluteSymbols(symbols.as_mut_ptr(), symbols.len());
+            if let Err(e) = check(LLVMOrcJITDylibDefine(dylib, unit)) {
+                // only taken over by the JIT on success
+                LLVMOrcDisposeMaterializationUnit(unit);
+                return Err(e);
+            }
+
+            // the JIT takes ownership of the buffer, even on failure
+            let buffer = LLVMCreateMemoryBufferWithMemoryRangeCopy(
+                object.as_ptr().cast(),
+                object.len(),
+                c"program".as_ptr(),
+            );
+            check(LLVMOrcLLJITAddObjectFile(jit.0, dylib, buffer))?;
+            Ok(jit)
+        }
+    }
+
+    /// The address of the function `name`, linking the object if necessary.
+    fn lookup(&self, name: &CStr) -> Result<usize, String> {
+        let mut address = 0;
+        check(unsafe { LLVMOrcLLJITLookup(self.0, &mut address, name.as_ptr()) })?;
+        Ok(address as usize)
+    }
+}
+
+impl Drop for Jit {
+    fn drop(&mut self) {
+        let _ = check(unsafe { LLVMOrcDisposeLLJIT(self.0) });
+    }
+}
+
+/// The message of an LLVM error, if there is one.
+fn check(error: LLVMErrorRef) -> Result<(), String> {
+    if error.is_null() {
+        return Ok(());
+    }
+    unsafe {
+        let raw = LLVMGetErrorMessage(error);
+        let message = CStr::from_ptr(raw).to_string_lossy().into_owned();
+        LLVMDisposeErrorMessage(raw);
+        Err(message)
+    }
+}
 
-    extern "C" fn putchar(ch: &u8, state: &mut LlvmState<R, W>) {
-        state.output.write_all(from_ref(ch)).unwrap();
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::LlvmState;
+
+    use crate::parser::parse;
+
+    #[test]
+    fn test_llvm_cache() {
+        let dir =
+            std::env::temp_dir().join(format!("brain_rust_llvm_cache_{}", std::process::id()));
+        let ops = parse(b"+++++[>+++++++++++++<-]>.,.").unwrap();
+        // the first run compiles the program, the second loads it
+        for cache_hit in [false, true] {
+            let mut input = &b"b"[..];
+            let mut output = vec![];
+            {
+                let mut state = LlvmState::new(&mut input, &mut output, true);
+                state.set_cache_dir(&dir);
+                assert_eq!(Ok(true), state.run(&ops));
+                assert_eq!(cache_hit, state.cache_hit);
+            }
+            assert_eq!(b"Ab", &output[..]);
+        }
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        fs::remove_dir_all(dir).unwrap();
     }
 }
@@ -1,88 +1,1329 @@
+use std::cell::Cell;
 use std::fs;
 use std::io;
-use std::io::Read;
-
-use clap::{Arg, ArgAction, Command};
+use std::io::{BufRead, BufReader, BufWriter, IsTerminal, Read, Write};
+use std::path::Path;
+#[cfg(feature = "llvm")]
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
 
-mod llvm_runner;
-mod optimizer;
-mod parser;
-mod runner;
-mod structs;
+use clap::builder::{PossibleValuesParser, RangedU64ValueParser};
+use clap::{Arg, ArgAction, ArgMatches, Command};
 
+#[cfg(feature = "cranelift")]
+use brain_rust::CraneliftState;
 #[cfg(feature = "llvm")]
-use llvm_runner::LlvmState;
+use brain_rust::LlvmState;
 
-use parser::parse;
-use runner::State;
-use structs::OpStream;
+use brain_rust::verify::{verify, verify_minified, VerifyError};
+use brain_rust::{
+    dump_cells_around, dump_tape, dump_tape_json, emit_asm, emit_c, emit_rust, emit_wasm, emit_wat,
+    format_source, minify, parse, parse_lossless, Bytecode, Config, Eof, Error, EscapeWriter,
+    Flush, FormatConfig, HexWriter, Op, OpStream, State,
+};
 
-fn main() {
+fn main() -> ExitCode {
+    // `brainrust FILES` is short for `brainrust run FILES`
     let command = Command::new("BrainRust")
-        .arg(
-            Arg::new("dry-run")
-                .action(ArgAction::SetTrue)
-                .short('n')
-                .long("dry-run")
-                .help("Don't actually execute the program"),
+        .args_conflicts_with_subcommands(true)
+        .subcommand_negates_reqs(true)
+        .subcommand(
+            Command::new("run")
+                .about("Execute programs (the default)")
+                .args(run_args()),
+        )
+        .subcommand(
+            Command::new("check")
+                .about("Parse programs without executing them")
+                .arg(Arg::new("FILES").action(ArgAction::Append).required(true)),
+        )
+        .subcommand(
+            Command::new("fmt")
+                .about("Reformat programs, keeping their comments")
+                .arg(
+                    Arg::new("write")
+                        .action(ArgAction::SetTrue)
+                        .short('w')
+                        .long("write")
+                        .help("Overwrite the files instead of printing them"),
+                )
+                .arg(
+                    Arg::new("check")
+                        .action(ArgAction::SetTrue)
+                        .long("check")
+                        .conflicts_with("write")
+                        .help("List the files that aren't formatted and fail if there are any"),
+                )
+                .arg(
+                    Arg::new("indent")
+                        .long("indent")
+                        .value_name("SPACES")
+                        .value_parser(clap::value_parser!(usize))
+                        .default_value("4")
+                        .help("Indent loop bodies by this many spaces"),
+                )
+                .arg(
+                    Arg::new("width")
+                        .long("width")
+                        .value_name("COLUMNS")
+                        .value_parser(clap::value_parser!(usize))
+                        .default_value("80")
+                        .help("Wrap lines of commands at this many columns"),
+                )
+                .arg(Arg::new("FILES").action(ArgAction::Append).required(true)),
+        )
+        .subcommand(
+            Command::new("minify")
+                .about("Shorten a program to the fewest commands with the same output")
+                .arg(output_arg())
+                .arg(
+                    Arg::new("check")
+                        .action(ArgAction::SetTrue)
+                        .long("check")
+                        .help("Run both versions on the input and compare them"),
+                )
+                .args(input_args())
+                .arg(Arg::new("FILE").required(true)),
+        )
+        .subcommand(
+            Command::new("compile")
+                .about("Translate a program into another language")
+                .arg(
+                    Arg::new("target")
+                        .long("target")
+                        .value_name("TARGET")
+                        .value_parser(["c", "rust", "rust-lib", "wat", "wasm", "x86-64"])
+                        .required(true)
+                        .help("Language to translate into"),
+                )
+                .arg(output_arg())
+                .args(settings_args())
+                .arg(Arg::new("FILE").required(true)),
+        )
+        .subcommand(
+            Command::new("disasm")
+                .about("List the bytecode instructions of a program")
+                .arg(no_optimize_arg())
+                .arg(Arg::new("FILE").required(true)),
+        )
+        .subcommand(
+            Command::new("debug")
+                .about("Execute a program, tracing every instruction to stderr")
+                .args(settings_args())
+                .arg(max_steps_arg())
+                .args(input_args())
+                .args(output_args())
+                .args(dump_tape_args())
+                .arg(Arg::new("FILE").required(true)),
+        )
+        .subcommand(
+            Command::new("repl")
+                .about("Run lines typed on stdin one after another on the same tape")
+                .args(settings_args())
+                .arg(max_steps_arg())
+                .args(input_args()),
         )
-        .arg(
-            Arg::new("no-optimize")
-                .action(ArgAction::SetTrue)
-                .short('0')
-                .long("no-optimize")
-                .help("Don't optimize before running"),
+        .subcommand(
+            Command::new("bench")
+                .about("Compare how fast the backends run a program, with output discarded")
+                .arg(
+                    Arg::new("runs")
+                        .short('r')
+                        .long("runs")
+                        .value_name("RUNS")
+                        .value_parser(clap::value_parser!(u32).range(1..))
+                        .default_value("3")
+                        .help("Run this many times per backend and report the fastest"),
+                )
+                .args(input_args())
+                .arg(Arg::new("FILE").required(true)),
         )
-        .arg(Arg::new("FILES").action(ArgAction::Append).required(true));
+        .subcommand(
+            Command::new("verify")
+                .about("Compare the results of the interpreter, the optimizer and the JIT")
+                .arg(max_steps_arg())
+                .args(input_args())
+                .arg(Arg::new("FILES").action(ArgAction::Append).required(true)),
+        )
+        .args(run_args());
+
+    let matches = command.get_matches();
+    let result = match matches.subcommand() {
+        Some(("run", matches)) => run(matches),
+        Some(("check", matches)) => check(matches),
+        Some(("fmt", matches)) => fmt(matches),
+        Some(("minify", matches)) => minify_program(matches),
+        Some(("compile", matches)) => compile(matches),
+        Some(("disasm", matches)) => disasm(matches),
+        Some(("debug", matches)) => debug(matches),
+        Some(("repl", matches)) => repl(matches),
+        Some(("bench", matches)) => bench(matches),
+        Some(("verify", matches)) => verify_programs(matches),
+        _ => run(&matches),
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(failure) => ExitCode::from(failure as u8),
+    }
+}
+
+/// Why the process fails, as its exit code. Usage errors found by clap exit
+/// with 2 as well.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Failure {
+    /// A check found a difference: an unformatted file, a minified program
+    /// that behaves differently or a backend that disagrees.
+    Check = 1,
+    Usage = 2,
+    Parse = 3,
+    Io = 4,
+    /// The pointer left the tape.
+    Runtime = 5,
+    OutOfSteps = 6,
+    NonTermination = 7,
+    /// A job ran longer than `--timeout`.
+    Timeout = 8,
+}
 
+impl From<Error> for Failure {
+    fn from(error: Error) -> Failure {
+        match error {
+            Error::Input(_) | Error::Output(_) => Failure::Io,
+            Error::OutOfSteps => Failure::OutOfSteps,
+            Error::PointerOutOfRange => Failure::Runtime,
+            Error::NonTermination => Failure::NonTermination,
+            Error::Cancelled => Failure::Timeout,
+        }
+    }
+}
+
+fn run(matches: &ArgMatches) -> Result<(), Failure> {
+    let dry_run = matches.get_flag("dry-run");
+    let keep_going = matches.get_flag("keep-going");
+    let settings = RunSettings::new(matches)?;
+
+    // all `-e` lines together form a single program
+    let expression = matches
+        .get_many::<String>("expression")
+        .map(|lines| lines.cloned().collect::<Vec<_>>().join("\n"));
+    let filenames: Vec<&str> = match expression {
+        Some(_) => vec!["-e"],
+        None => matches
+            .get_many::<String>("FILES")
+            .unwrap()
+            .map(String::as_str)
+            .collect(),
+    };
+    let load_program = |filename: &str| {
+        let ops = match expression {
+            Some(ref code) => parse_program(filename, code.as_bytes()),
+            None => load(filename),
+        }?;
+        let mut opstream = OpStream { ops };
+        if settings.optimize && settings.backend != "llvm" {
+            opstream.optimize();
+        }
+        Ok(opstream)
+    };
+
+    if let Some(&jobs) = matches.get_one::<u32>("jobs") {
+        let programs = filenames
+            .iter()
+            .map(|&filename| (filename, load_program(filename)))
+            .collect();
+        return run_jobs(matches, &settings, programs, jobs as usize);
+    }
+
+    let mut input = open_input(matches)?;
+    let mut output = open_output(matches)?;
+    let mut failure = None;
+    for filename in filenames {
+        let result = load_program(filename).and_then(|opstream| {
+            if dry_run {
+                return Ok(());
+            }
+            let (result, tape) = settings.execute(&opstream, &mut input, &mut output, None);
+            if let Some(tape) = tape {
+                eprint!("{}", tape);
+            }
+            result.map(|_| ()).map_err(|e| {
+                eprintln!("Error while running {}: {}", filename, e);
+                Failure::from(e)
+            })
+        });
+        if let Err(e) = result {
+            failure.get_or_insert(e);
+            if !keep_going {
+                break;
+            }
+        }
+    }
+    // after a failure, dropping the output still writes what is left
+    match failure {
+        Some(e) => Err(e),
+        None => output.finish(),
+    }
+}
+
+/// The options of `run` that say how to execute a program, owned so that
+/// jobs can take them to other threads.
+#[derive(Clone)]
+struct RunSettings {
+    backend: String,
+    optimize: bool,
+    config: Config,
+    flush: Flush,
+    dump_tape: Option<String>,
     #[cfg(feature = "llvm")]
-    let command = command.arg(
-        Arg::new("llvm")
-            .action(ArgAction::SetTrue)
-            .short('l')
-            .long("llvm")
-            .help("Execute using LLVM JIT"),
-    );
+    jit_cache: Option<PathBuf>,
+}
 
-    let matches = command.get_matches();
+impl RunSettings {
+    /// Fails for settings that the backend doesn't support.
+    fn new(matches: &ArgMatches) -> Result<RunSettings, Failure> {
+        let backend = if cfg!(feature = "llvm") && matches.get_flag("llvm") {
+            "llvm"
+        } else {
+            matches.get_one::<String>("backend").unwrap().as_str()
+        };
+        let unsupported = match backend {
+            "llvm" => &["max-steps", "tape-size", "timeout"][..],
+            "cranelift" => &["max-steps", "timeout"][..],
+            _ => &[],
+        };
+        for &option in unsupported {
+            if matches.contains_id(option) {
+                eprintln!("The {} backend doesn't support --{}", backend, option);
+                return Err(Failure::Usage);
+            }
+        }
+        Ok(RunSettings {
+            backend: backend.to_string(),
+            optimize: !matches.get_flag("no-optimize"),
+            config: config(matches),
+            flush: flush_policy(matches),
+            dump_tape: dump_tape_format(matches),
+            #[cfg(feature = "llvm")]
+            jit_cache: matches.get_one::<PathBuf>("jit-cache").cloned(),
+        })
+    }
+
+    /// Runs a program loaded by `run` and returns the result and the dump of
+    /// the tape, if asked for. The interpreters stop once `cancel` is set.
+    fn execute<R: Read, W: Write>(
+        &self,
+        opstream: &OpStream,
+        input: &mut R,
+        output: &mut W,
+        cancel: Option<&AtomicBool>,
+    ) -> (Result<bool, Error>, Option<String>) {
+        let format = self.dump_tape.as_deref();
+        match self.backend.as_str() {
+            #[cfg(feature = "llvm")]
+            "llvm" => {
+                let mut state = LlvmState::new(input, output, self.optimize);
+                state.set_eof(self.config.eof);
+                state.set_flush(self.flush);
+                if let Some(dir) = &self.jit_cache {
+                    state.set_cache_dir(dir);
+                }
+                let result = state.run(opstream.get());
+                (result, tape_dump(format, state.memory(), state.index()))
+            }
+            #[cfg(feature = "cranelift")]
+            "cranelift" => {
+                let mut state = CraneliftState::new(input, output, self.optimize);
+                if let Some(tape_size) = self.config.tape_size {
+                    state.set_tape_size(tape_size);
+                }
+                state.set_eof(self.config.eof);
+                state.set_flush(self.flush);
+                let result = state.run(opstream.get());
+                (result, tape_dump(format, state.memory(), state.index()))
+            }
+            _ => {
+                let mut state = State::with_config(input, output, &self.config);
+                state.set_flush(self.flush);
+                if let Some(cancel) = cancel {
+                    state.set_cancel(cancel);
+                }
+                let result = if self.backend == "bytecode" {
+                    state.run_bytecode(&Bytecode::compile(opstream.get()))
+                } else {
+                    state.run(opstream.get())
+                };
+                (result, tape_dump(format, state.memory(), state.index()))
+            }
+        }
+    }
+}
+
+/// How a program run by a job ended.
+enum JobOutcome {
+    Finished {
+        output: Vec<u8>,
+        result: Result<bool, Error>,
+        tape: Option<String>,
+    },
+    TimedOut,
+    /// The job panicked.
+    Crashed,
+}
 
+/// Runs the programs on `jobs` threads, each with its own input and with its
+/// output captured. Afterwards writes their output and errors in the order of
+/// the files, followed by a summary on stderr.
+fn run_jobs(
+    matches: &ArgMatches,
+    settings: &RunSettings,
+    programs: Vec<(&str, Result<OpStream, Failure>)>,
+    jobs: usize,
+) -> Result<(), Failure> {
     let dry_run = matches.get_flag("dry-run");
-    let no_optimize = matches.get_flag("no-optimize");
-    let use_llvm = cfg!(feature = "llvm") && matches.get_flag("llvm");
+    let timeout = matches
+        .get_one::<u64>("timeout")
+        .map(|&secs| Duration::from_secs(secs));
+    // with `--input` or `--input-string` all programs get the same input
+    let shared_input = if matches.contains_id("input") || matches.contains_id("input-string") {
+        Some(read_input(matches)?)
+    } else {
+        None
+    };
+    let mut queue = Vec::new();
+    for (i, (filename, program)) in programs.iter().enumerate() {
+        if let (Ok(opstream), false) = (program, dry_run) {
+            let input = match &shared_input {
+                Some(input) => input.clone(),
+                None => job_input(filename)?,
+            };
+            queue.push((i, opstream.clone(), input));
+        }
+    }
 
+    let queue = Mutex::new(queue.into_iter());
+    let (sender, receiver) = mpsc::channel();
+    thread::scope(|scope| {
+        for _ in 0..jobs {
+            let sender = sender.clone();
+            let queue = &queue;
+            scope.spawn(move || loop {
+                let Some((i, opstream, input)) = queue.lock().unwrap().next() else {
+                    break;
+                };
+                let outcome = run_job(settings, opstream, input, timeout);
+                sender.send((i, outcome)).unwrap();
+            });
+        }
+    });
+    drop(sender);
+    let mut outcomes: Vec<Option<JobOutcome>> = programs.iter().map(|_| None).collect();
+    for (i, outcome) in receiver {
+        outcomes[i] = Some(outcome);
+    }
+
+    let mut output = open_output(matches)?;
+    let mut failure = None;
+    let mut summary = vec![];
+    for ((filename, program), outcome) in programs.iter().zip(outcomes) {
+        let result = match (program, outcome) {
+            (Err(e), _) => Err(*e),
+            (Ok(_), None) => Ok(()),
+            (Ok(_), Some(JobOutcome::TimedOut)) => Err(Failure::Timeout),
+            (Ok(_), Some(JobOutcome::Crashed)) => {
+                eprintln!("Error while running {}: The job crashed", filename);
+                Err(Failure::Runtime)
+            }
+            (
+                Ok(_),
+                Some(JobOutcome::Finished {
+                    output: data,
+                    result,
+                    tape,
+                }),
+            ) => {
+                let written = output.write_all(&data).and_then(|()| output.flush());
+                if let Some(tape) = tape {
+                    eprint!("{}", tape);
+                }
+                match (result, written) {
+                    (Err(e), _) => {
+                        eprintln!("Error while running {}: {}", filename, e);
+                        Err(Failure::from(e))
+                    }
+                    (Ok(_), Err(e)) => {
+                        eprintln!("Error while writing the output of {}: {}", filename, e);
+                        Err(Failure::Io)
+                    }
+                    (Ok(_), Ok(())) => Ok(()),
+                }
+            }
+        };
+        let status = match result {
+            Ok(()) => "passed",
+            Err(Failure::Timeout) => "timed out",
+            Err(_) => "failed",
+        };
+        summary.push((status, filename));
+        if let Err(e) = result {
+            failure.get_or_insert(e);
+        }
+    }
+    if let Err(e) = output.finish() {
+        failure.get_or_insert(e);
+    }
+
+    for (status, filename) in &summary {
+        eprintln!("{:9}  {}", status, filename);
+    }
+    let count = |status| summary.iter().filter(|(s, _)| *s == status).count();
+    eprintln!(
+        "{} passed, {} failed, {} timed out",
+        count("passed"),
+        count("failed"),
+        count("timed out")
+    );
+    failure.map_or(Ok(()), Err)
+}
+
+/// Runs a program on a thread of its own, which is cancelled if it takes
+/// longer than `timeout`.
+fn run_job(
+    settings: &RunSettings,
+    opstream: OpStream,
+    input: Vec<u8>,
+    timeout: Option<Duration>,
+) -> JobOutcome {
+    let settings = settings.clone();
+    let cancel = Arc::new(AtomicBool::new(false));
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn({
+        let cancel = Arc::clone(&cancel);
+        move || {
+            let mut output = vec![];
+            let (result, tape) =
+                settings.execute(&opstream, &mut &input[..], &mut output, Some(&cancel));
+            let _ = sender.send(JobOutcome::Finished {
+                output,
+                result,
+                tape,
+            });
+        }
+    });
+    // the thread only drops the sender without a result if it panicked
+    match timeout {
+        Some(timeout) => match receiver.recv_timeout(timeout) {
+            Ok(outcome) => outcome,
+            Err(RecvTimeoutError::Timeout) => {
+                cancel.store(true, Ordering::Relaxed);
+                // wait for the interpreter to stop, so it doesn't use a core
+                // for the remaining jobs
+                let _ = receiver.recv();
+                JobOutcome::TimedOut
+            }
+            Err(RecvTimeoutError::Disconnected) => JobOutcome::Crashed,
+        },
+        None => receiver.recv().unwrap_or(JobOutcome::Crashed),
+    }
+}
+
+/// The input of a job: `NAME.in` next to the program `NAME.bf`, like in the
+/// conformance tests, or nothing.
+fn job_input(filename: &str) -> Result<Vec<u8>, Failure> {
+    let path = Path::new(filename).with_extension("in");
+    if filename == "-" || filename == "-e" || !path.is_file() {
+        return Ok(vec![]);
+    }
+    fs::read(&path).map_err(|e| {
+        eprintln!("Error while reading {}: {}", path.display(), e);
+        Failure::Io
+    })
+}
+
+fn check(matches: &ArgMatches) -> Result<(), Failure> {
+    let mut failure = None;
+    for filename in matches.get_many::<String>("FILES").unwrap() {
+        if let Err(e) = load(filename) {
+            failure.get_or_insert(e);
+        }
+    }
+    failure.map_or(Ok(()), Err)
+}
+
+fn fmt(matches: &ArgMatches) -> Result<(), Failure> {
+    let config = FormatConfig {
+        indent: *matches.get_one::<usize>("indent").unwrap(),
+        width: *matches.get_one::<usize>("width").unwrap(),
+    };
+    let mut failure = None;
     for filename in matches.get_many::<String>("FILES").unwrap() {
         let buffer = match read_file(filename) {
             Ok(v) => v,
             Err(e) => {
                 eprintln!("Error while reading {}: {}", filename, e);
+                failure.get_or_insert(Failure::Io);
                 continue;
             }
         };
-        let ops = match parse(&buffer) {
+        let nodes = match parse_lossless(&buffer) {
             Ok(v) => v,
             Err(e) => {
                 eprintln!("Error while parsing {}: {}", filename, e);
+                failure.get_or_insert(Failure::Parse);
+                continue;
+            }
+        };
+        let formatted = format_source(&nodes, &config);
+        let result = if matches.get_flag("check") {
+            if formatted != buffer {
+                println!("{}", filename);
+                failure.get_or_insert(Failure::Check);
+            }
+            Ok(())
+        } else if matches.get_flag("write") {
+            fs::write(filename, formatted)
+        } else {
+            io::stdout().write_all(&formatted)
+        };
+        if let Err(e) = result {
+            eprintln!("Error while writing {}: {}", filename, e);
+            failure.get_or_insert(Failure::Io);
+        }
+    }
+    failure.map_or(Ok(()), Err)
+}
+
+fn minify_program(matches: &ArgMatches) -> Result<(), Failure> {
+    let ops = load(matches.get_one::<String>("FILE").unwrap())?;
+    let minified = minify(&ops);
+    if matches.get_flag("check") {
+        let input = read_input(matches)?;
+        if let Err(e) = verify_minified(&OpStream { ops }, &minified, &input) {
+            eprintln!("{}", e);
+            return Err(Failure::Check);
+        }
+    }
+    write_output(matches, minified.to_string().as_bytes())
+}
+
+fn compile(matches: &ArgMatches) -> Result<(), Failure> {
+    let ops = load(matches.get_one::<String>("FILE").unwrap())?;
+    let mut opstream = OpStream { ops };
+    if !matches.get_flag("no-optimize") {
+        opstream.optimize();
+    }
+    let config = config(matches);
+    let target = matches.get_one::<String>("target").unwrap().as_str();
+    let max_tape_size = match target {
+        "wat" | "wasm" => 1 << 32,
+        "x86-64" => i32::MAX as u64,
+        _ => u64::MAX,
+    };
+    if config
+        .tape_size
+        .is_some_and(|size| size as u64 > max_tape_size)
+    {
+        eprintln!("The tape is too large for {}", target);
+        return Err(Failure::Usage);
+    }
+    let code = match target {
+        "rust" => emit_rust(opstream.get(), &config, false).into_bytes(),
+        "rust-lib" => emit_rust(opstream.get(), &config, true).into_bytes(),
+        "wat" => emit_wat(opstream.get(), &config).into_bytes(),
+        "wasm" => emit_wasm(opstream.get(), &config),
+        "x86-64" => emit_asm(opstream.get(), &config).into_bytes(),
+        _ => emit_c(opstream.get(), &config).into_bytes(),
+    };
+    write_output(matches, &code)
+}
+
+fn disasm(matches: &ArgMatches) -> Result<(), Failure> {
+    let ops = load(matches.get_one::<String>("FILE").unwrap())?;
+    let mut opstream = OpStream { ops };
+    if !matches.get_flag("no-optimize") {
+        opstream.optimize();
+    }
+    let bytecode = Bytecode::compile(opstream.get());
+    for (pc, instr) in bytecode.instructions().iter().enumerate() {
+        println!("{:6}  {}", pc, instr);
+    }
+    Ok(())
+}
+
+fn debug(matches: &ArgMatches) -> Result<(), Failure> {
+    let filename = matches.get_one::<String>("FILE").unwrap();
+    let ops = load(filename)?;
+    let mut opstream = OpStream { ops };
+    if !matches.get_flag("no-optimize") {
+        opstream.optimize();
+    }
+    let bytecode = Bytecode::compile(opstream.get());
+    let code = bytecode.instructions();
+    let mut input = open_input(matches)?;
+    let mut output = open_output(matches)?;
+    let mut state = State::with_config(&mut input, &mut output, &config(matches));
+    state.set_flush(flush_policy(matches));
+    let mut step = 0u64;
+    let result = state.run_bytecode_traced(&bytecode, |pc, state| {
+        let cell = state.memory().get(state.index()).copied().unwrap_or(0);
+        eprintln!(
+            "{:8} {:6}  {:32} pointer {:6} cell {:3}",
+            step,
+            pc,
+            code[pc].to_string(),
+            state.index(),
+            cell
+        );
+        step += 1;
+    });
+    if let Some(tape) = tape_dump(
+        dump_tape_format(matches).as_deref(),
+        state.memory(),
+        state.index(),
+    ) {
+        eprint!("{}", tape);
+    }
+    drop(state);
+    result.map_err(|e| {
+        eprintln!("Error while running {}: {}", filename, e);
+        Failure::from(e)
+    })?;
+    output.finish()
+}
+
+fn repl(matches: &ArgMatches) -> Result<(), Failure> {
+    let no_optimize = matches.get_flag("no-optimize");
+    let mut input = open_input(matches)?;
+    let last_byte = Cell::new(b'\n');
+    let mut output = LastByte {
+        inner: io::stdout(),
+        last: &last_byte,
+    };
+    let mut state = State::with_config(&mut input, &mut output, &config(matches));
+    let stdin = io::stdin();
+    let interactive = stdin.is_terminal();
+    let mut source = vec![];
+    loop {
+        if interactive {
+            print!("{}", if source.is_empty() { "bf> " } else { "... " });
+            io::stdout().flush().map_err(|_| Failure::Io)?;
+        }
+        // the lock is released before the program reads its input from stdin
+        let read = stdin.lock().read_until(b'\n', &mut source);
+        match read {
+            Ok(0) if source.is_empty() => return Ok(()),
+            Ok(0) => return parse_program("stdin", &source).map(|_| ()),
+            Ok(_) => {}
+            Err(e) => {
+                eprintln!("Error while reading stdin: {}", e);
+                return Err(Failure::Io);
+            }
+        }
+        let ops = match parse(&source) {
+            // keep reading until the loops are closed
+            Err(e) if e.starts_with("Missing ]") => continue,
+            Err(e) => {
+                eprintln!("Error while parsing: {}", e);
+                source.clear();
                 continue;
             }
+            Ok(ops) => ops,
         };
+        source.clear();
         let mut opstream = OpStream { ops };
-        if !(use_llvm || no_optimize) {
+        if !no_optimize {
             opstream.optimize();
         }
-        if !dry_run {
-            if use_llvm {
-                #[cfg(feature = "llvm")]
-                LlvmState::new(&mut io::stdin(), &mut io::stdout(), !no_optimize)
-                    .run(opstream.get());
-            } else {
-                State::new(&mut io::stdin(), &mut io::stdout()).run(opstream.get());
+        let result = state.run(opstream.get());
+        if last_byte.replace(b'\n') != b'\n' {
+            println!();
+        }
+        if let Err(e) = result {
+            eprintln!("Error while running: {}", e);
+        }
+        println!("{}", dump_cells_around(state.memory(), state.index(), 4));
+    }
+}
+
+/// Remembers the last byte written, so that the REPL can start a new line
+/// after output without a line break.
+struct LastByte<'a, W: Write> {
+    inner: W,
+    last: &'a Cell<u8>,
+}
+
+impl<W: Write> Write for LastByte<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        if let Some(&last) = buf[..written].last() {
+            self.last.set(last);
+        }
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+fn bench(matches: &ArgMatches) -> Result<(), Failure> {
+    let ops = load(matches.get_one::<String>("FILE").unwrap())?;
+    let input = read_input(matches)?;
+    let runs = *matches.get_one::<u32>("runs").unwrap();
+    let opstream = OpStream { ops };
+
+    let results: Vec<_> = bench_backends(&opstream)
+        .into_iter()
+        .map(|(backend, run)| (backend, fastest_run(&run, &input, runs)))
+        .collect();
+    let fastest = results
+        .iter()
+        .filter_map(|(_, result)| result.as_ref().ok().map(Timing::total))
+        .min();
+
+    println!(
+        "{:32} {:>12} {:>12} {:>12} {:>9}",
+        "backend", "compile", "run", "total", "relative"
+    );
+    let mut failure = None;
+    for (backend, result) in results {
+        match result {
+            Ok(best) => println!(
+                "{:32} {:>9.3} ms {:>9.3} ms {:>9.3} ms {:>8.2}x",
+                backend,
+                best.compile.as_secs_f64() * 1000.0,
+                best.run.as_secs_f64() * 1000.0,
+                best.total().as_secs_f64() * 1000.0,
+                best.total().as_secs_f64() / fastest.unwrap().as_secs_f64().max(f64::MIN_POSITIVE)
+            ),
+            Err(e) => {
+                println!("{:32} {}", backend, e);
+                failure.get_or_insert(Failure::from(e));
+            }
+        }
+    }
+    failure.map_or(Ok(()), Err)
+}
+
+/// How long a backend took to prepare a program, including the optimizer, and
+/// to run it.
+#[derive(Clone, Copy)]
+struct Timing {
+    compile: Duration,
+    run: Duration,
+}
+
+impl Timing {
+    fn total(&self) -> Duration {
+        self.compile + self.run
+    }
+}
+
+/// The fastest of `runs` runs, or the first error.
+fn fastest_run(run: &BenchRun, input: &[u8], runs: u32) -> Result<Timing, Error> {
+    let mut best = run(input)?;
+    for _ in 1..runs {
+        let timing = run(input)?;
+        if timing.total() < best.total() {
+            best = timing;
+        }
+    }
+    Ok(best)
+}
+
+type BenchRun<'a> = Box<dyn Fn(&[u8]) -> Result<Timing, Error> + 'a>;
+
+/// Every backend at every optimization level, as functions that run the
+/// program on the given input with the output going nowhere.
+fn bench_backends(opstream: &OpStream) -> Vec<(&'static str, BenchRun<'_>)> {
+    let interpreter = |optimize: bool, bytecode: bool| -> BenchRun {
+        Box::new(move |mut input: &[u8]| {
+            let start = Instant::now();
+            let mut opstream = opstream.clone();
+            if optimize {
+                opstream.optimize();
+            }
+            let code = bytecode.then(|| Bytecode::compile(opstream.get()));
+            let compile = start.elapsed();
+            let mut sink = io::sink();
+            let mut state = State::new(&mut input, &mut sink);
+            let start = Instant::now();
+            match code {
+                Some(code) => state.run_bytecode(&code)?,
+                None => state.run(opstream.get())?,
             };
+            let run = start.elapsed();
+            Ok(Timing { compile, run })
+        })
+    };
+    #[allow(unused_mut)]
+    let mut backends = vec![
+        ("interpreter", interpreter(false, false)),
+        ("optimized interpreter", interpreter(true, false)),
+        ("bytecode interpreter", interpreter(false, true)),
+        ("optimized bytecode interpreter", interpreter(true, true)),
+    ];
+
+    // like `run`, the LLVM JIT leaves optimizing to LLVM
+    #[cfg(feature = "llvm")]
+    for (backend, optimize) in [("LLVM JIT", false), ("optimized LLVM JIT", true)] {
+        let run: BenchRun = Box::new(move |mut input: &[u8]| {
+            let mut sink = io::sink();
+            let mut state = LlvmState::new(&mut input, &mut sink, optimize);
+            let start = Instant::now();
+            state.run(opstream.get())?;
+            let total = start.elapsed();
+            let compile = state.compile_time();
+            Ok(Timing {
+                compile,
+                run: total - compile,
+            })
+        });
+        backends.push((backend, run));
+    }
+
+    #[cfg(feature = "cranelift")]
+    for (backend, optimize) in [("Cranelift JIT", false), ("optimized Cranelift JIT", true)] {
+        let run: BenchRun = Box::new(move |mut input: &[u8]| {
+            let start = Instant::now();
+            let mut opstream = opstream.clone();
+            if optimize {
+                opstream.optimize();
+            }
+            let optimizer = start.elapsed();
+            let mut sink = io::sink();
+            let mut state = CraneliftState::new(&mut input, &mut sink, optimize);
+            let start = Instant::now();
+            state.run(opstream.get())?;
+            let total = start.elapsed();
+            let compile = state.compile_time();
+            Ok(Timing {
+                compile: optimizer + compile,
+                run: total - compile,
+            })
+        });
+        backends.push((backend, run));
+    }
+
+    backends
+}
+
+fn verify_programs(matches: &ArgMatches) -> Result<(), Failure> {
+    let input = read_input(matches)?;
+    let max_steps = matches.get_one::<u64>("max-steps").copied();
+    let mut failure = None;
+    for filename in matches.get_many::<String>("FILES").unwrap() {
+        let ops = match load(filename) {
+            Ok(ops) => ops,
+            Err(e) => {
+                failure.get_or_insert(e);
+                continue;
+            }
+        };
+        match verify(&OpStream { ops }, &input, max_steps) {
+            Ok(()) => println!("{}: OK", filename),
+            Err(e) => {
+                println!("{}: {}", filename, e);
+                failure.get_or_insert(match e {
+                    VerifyError::Difference(_) => Failure::Check,
+                    VerifyError::Reference(e) => Failure::from(e),
+                });
+            }
         }
     }
+    failure.map_or(Ok(()), Err)
+}
+
+/// Options of `run`, which are also accepted without a subcommand.
+fn run_args() -> Vec<Arg> {
+    let backends = [
+        ("interpreter", true),
+        ("bytecode", true),
+        ("llvm", cfg!(feature = "llvm")),
+        ("cranelift", cfg!(feature = "cranelift")),
+    ];
+    let backends = backends
+        .into_iter()
+        .filter_map(|(backend, enabled)| enabled.then_some(backend));
+
+    let mut args = vec![
+        Arg::new("dry-run")
+            .action(ArgAction::SetTrue)
+            .short('n')
+            .long("dry-run")
+            .help("Don't actually execute the program"),
+        Arg::new("keep-going")
+            .action(ArgAction::SetTrue)
+            .short('k')
+            .long("keep-going")
+            .help("Run the remaining files after one fails"),
+    ];
+    args.extend(settings_args());
+    args.push(
+        Arg::new("backend")
+            .long("backend")
+            .value_name("BACKEND")
+            .value_parser(PossibleValuesParser::new(backends))
+            .default_value("interpreter")
+            .help("Execute using this backend"),
+    );
+    args.push(max_steps_arg());
+    args.push(
+        Arg::new("jobs")
+            .short('j')
+            .long("jobs")
+            .value_name("N")
+            .value_parser(clap::value_parser!(u32).range(1..))
+            .conflicts_with("keep-going")
+            .help(
+                "Run N files at the same time, each with its own input, and summarize the results",
+            ),
+    );
+    args.push(
+        Arg::new("timeout")
+            .long("timeout")
+            .value_name("SECS")
+            .value_parser(clap::value_parser!(u64).range(1..))
+            .requires("jobs")
+            .help("With --jobs, give up on programs that run longer than this"),
+    );
+    args.extend(input_args());
+    args.extend(output_args());
+    args.extend(dump_tape_args());
+    args.push(
+        Arg::new("expression")
+            .short('e')
+            .value_name("CODE")
+            .action(ArgAction::Append)
+            .conflicts_with("FILES")
+            .help("Run this program text, joined by line breaks if given repeatedly"),
+    );
+    args.push(
+        Arg::new("FILES")
+            .action(ArgAction::Append)
+            .required_unless_present("expression")
+            .help("Programs to run, - for stdin"),
+    );
+    #[cfg(feature = "llvm")]
+    args.push(
+        Arg::new("llvm")
+            .action(ArgAction::SetTrue)
+            .short('l')
+            .long("llvm")
+            .conflicts_with("backend")
+            .help("Execute using LLVM JIT, same as --backend llvm"),
+    );
+    #[cfg(feature = "llvm")]
+    args.push(
+        Arg::new("jit-cache")
+            .long("jit-cache")
+            .value_name("DIR")
+            .value_parser(clap::value_parser!(PathBuf))
+            .help("Keep programs compiled by the LLVM JIT in this directory to reuse them"),
+    );
+    args
+}
+
+/// Options shared by running and compiling programs.
+fn settings_args() -> [Arg; 3] {
+    [
+        no_optimize_arg(),
+        Arg::new("tape-size")
+            .long("tape-size")
+            .value_name("CELLS")
+            .value_parser(RangedU64ValueParser::<usize>::new().range(1..))
+            .help("Stop when the pointer leaves this many cells"),
+        Arg::new("eof")
+            .long("eof")
+            .value_name("MODE")
+            .value_parser(["stop", "unchanged", "zero", "minus-one"])
+            .default_value("stop")
+            .help("What reading does at the end of the input"),
+    ]
+}
+
+fn no_optimize_arg() -> Arg {
+    Arg::new("no-optimize")
+        .action(ArgAction::SetTrue)
+        .short('0')
+        .long("no-optimize")
+        .help("Don't optimize the program")
+}
+
+fn max_steps_arg() -> Arg {
+    Arg::new("max-steps")
+        .long("max-steps")
+        .value_name("STEPS")
+        .value_parser(clap::value_parser!(u64))
+        .help("Stop the interpreter after this many steps")
 }
 
+/// Options for the input of programs, which is stdin otherwise.
+fn input_args() -> [Arg; 2] {
+    [
+        Arg::new("input")
+            .long("input")
+            .value_name("FILE")
+            .help("Read the program's input from this file"),
+        Arg::new("input-string")
+            .long("input-string")
+            .value_name("TEXT")
+            .value_parser(unescape)
+            .conflicts_with("input")
+            .help("Use this text as the program's input, with escapes like \\n and \\xff"),
+    ]
+}
+
+/// Resolves the escape sequences `\\`, `\n`, `\r`, `\t`, `\0` and `\xNN`.
+fn unescape(text: &str) -> Result<Vec<u8>, String> {
+    let mut bytes = text.bytes();
+    let mut unescaped = vec![];
+    while let Some(c) = bytes.next() {
+        if c != b'\\' {
+            unescaped.push(c);
+            continue;
+        }
+        unescaped.push(match bytes.next() {
+            Some(b'\\') => b'\\',
+            Some(b'n') => b'\n',
+            Some(b'r') => b'\r',
+            Some(b't') => b'\t',
+            Some(b'0') => 0,
+            Some(b'x') => {
+                let hex: String = bytes.by_ref().take(2).map(char::from).collect();
+                match u8::from_str_radix(&hex, 16) {
+                    Ok(byte) if hex.len() == 2 => byte,
+                    _ => return Err(format!("\\x needs two hex digits, not {:?}", hex)),
+                }
+            }
+            Some(c) => return Err(format!("Unknown escape sequence \\{}", char::from(c))),
+            None => return Err("Incomplete escape sequence at the end".into()),
+        });
+    }
+    Ok(unescaped)
+}
+
+/// Options for the output of programs, which is stdout as it is otherwise.
+fn output_args() -> [Arg; 3] {
+    [
+        output_arg(),
+        Arg::new("flush")
+            .long("flush")
+            .value_name("WHEN")
+            .value_parser(["newline", "input", "exit"])
+            .default_value("newline")
+            .help("Write buffered output after line breaks and before reading input, only before reading input, or only at the end"),
+        Arg::new("output-format")
+            .long("output-format")
+            .value_name("FORMAT")
+            .value_parser(["raw", "hex", "escaped"])
+            .default_value("raw")
+            .help(
+                "Write the program's output as it is, as a hex dump or with escaped binary bytes",
+            ),
+    ]
+}
+
+fn dump_tape_args() -> [Arg; 2] {
+    [
+        Arg::new("dump-tape")
+            .action(ArgAction::SetTrue)
+            .long("dump-tape")
+            .help("Print the pointer and the non-zero cells to stderr after the run"),
+        Arg::new("dump-tape-format")
+            .long("dump-tape-format")
+            .value_name("FORMAT")
+            .value_parser(["table", "json"])
+            .help("Dump the tape in this format, by default a table"),
+    ]
+}
+
+/// The format of `--dump-tape`, if the tape is to be dumped. Giving a format
+/// is enough to ask for it.
+fn dump_tape_format(matches: &ArgMatches) -> Option<String> {
+    match matches.get_one::<String>("dump-tape-format") {
+        Some(format) => Some(format.clone()),
+        None => matches.get_flag("dump-tape").then(|| "table".to_string()),
+    }
+}
+
+/// The tape dumped in the format given to `--dump-tape`, if any.
+fn tape_dump(format: Option<&str>, memory: &[u8], index: usize) -> Option<String> {
+    match format? {
+        "json" => Some(dump_tape_json(memory, index)),
+        _ => Some(dump_tape(memory, index)),
+    }
+}
+
+fn output_arg() -> Arg {
+    Arg::new("output")
+        .short('o')
+        .long("output")
+        .value_name("FILE")
+        .help("Write to this file instead of stdout")
+}
+
+/// The settings given by [`settings_args`] and, where the subcommand takes
+/// it, `--max-steps`.
+fn config(matches: &ArgMatches) -> Config {
+    Config {
+        max_steps: matches
+            .try_get_one::<u64>("max-steps")
+            .ok()
+            .flatten()
+            .copied(),
+        tape_size: matches.get_one::<usize>("tape-size").copied(),
+        eof: eof_mode(matches.get_one::<String>("eof").unwrap()),
+    }
+}
+
+fn flush_policy(matches: &ArgMatches) -> Flush {
+    match matches.get_one::<String>("flush").unwrap().as_str() {
+        "input" => Flush::Input,
+        "exit" => Flush::Exit,
+        _ => Flush::Newline,
+    }
+}
+
+fn eof_mode(name: &str) -> Eof {
+    match name {
+        "unchanged" => Eof::Unchanged,
+        "zero" => Eof::Zero,
+        "minus-one" => Eof::MinusOne,
+        _ => Eof::Stop,
+    }
+}
+
+/// Reads a file, or stdin for `-`.
 fn read_file(filename: &str) -> Result<Vec<u8>, io::Error> {
     let mut buffer = Vec::new();
+    if filename == "-" {
+        io::stdin().read_to_end(&mut buffer)?;
+        return Ok(buffer);
+    }
     fs::File::open(filename)?.read_to_end(&mut buffer)?;
     Ok(buffer)
 }
+
+/// Reads and parses a program, reporting errors on stderr.
+fn load(filename: &str) -> Result<Vec<Op>, Failure> {
+    match read_file(filename) {
+        Ok(buffer) => parse_program(filename, &buffer),
+        Err(e) => {
+            eprintln!("Error while reading {}: {}", filename, e);
+            Err(Failure::Io)
+        }
+    }
+}
+
+/// Parses a program, reporting errors on stderr.
+fn parse_program(filename: &str, buffer: &[u8]) -> Result<Vec<Op>, Failure> {
+    parse(buffer).map_err(|e| {
+        eprintln!("Error while parsing {}: {}", filename, e);
+        Failure::Parse
+    })
+}
+
+/// The input given by [`input_args`], stdin by default.
+fn open_input(matches: &ArgMatches) -> Result<Box<dyn Read>, Failure> {
+    if let Some(input) = matches.get_one::<Vec<u8>>("input-string") {
+        return Ok(Box::new(io::Cursor::new(input.clone())));
+    }
+    match matches.get_one::<String>("input") {
+        Some(filename) => match fs::File::open(filename) {
+            Ok(file) => Ok(Box::new(BufReader::new(file))),
+            Err(e) => {
+                eprintln!("Error while reading {}: {}", filename, e);
+                Err(Failure::Io)
+            }
+        },
+        None => Ok(Box::new(io::stdin())),
+    }
+}
+
+/// Reads all of the input at once, for programs that are run more than once.
+fn read_input(matches: &ArgMatches) -> Result<Vec<u8>, Failure> {
+    let mut input = Vec::new();
+    if let Err(e) = open_input(matches)?.read_to_end(&mut input) {
+        eprintln!("Error while reading input: {}", e);
+        return Err(Failure::Io);
+    }
+    Ok(input)
+}
+
+/// The output given by [`output_args`].
+fn open_output(matches: &ArgMatches) -> Result<ProgramOutput, Failure> {
+    let output: Box<dyn Write> = match matches.get_one::<String>("output") {
+        Some(filename) => match fs::File::create(filename) {
+            Ok(file) => Box::new(BufWriter::new(file)),
+            Err(e) => {
+                eprintln!("Error while writing {}: {}", filename, e);
+                return Err(Failure::Io);
+            }
+        },
+        None => Box::new(io::stdout()),
+    };
+    Ok(
+        match matches.get_one::<String>("output-format").unwrap().as_str() {
+            "hex" => ProgramOutput::Hex(HexWriter::new(output)),
+            "escaped" => ProgramOutput::Escaped(EscapeWriter::new(output)),
+            _ => ProgramOutput::Raw(output),
+        },
+    )
+}
+
+/// The output of programs in the format given by `--output-format`.
+enum ProgramOutput {
+    Raw(Box<dyn Write>),
+    Hex(HexWriter<Box<dyn Write>>),
+    Escaped(EscapeWriter<Box<dyn Write>>),
+}
+
+impl ProgramOutput {
+    /// Writes what is left: the last line of a hex dump and the buffer of
+    /// the output file. Dropping the output does so too, but ignores errors.
+    fn finish(mut self) -> Result<(), Failure> {
+        let result = match &mut self {
+            ProgramOutput::Hex(writer) => writer.finish(),
+            _ => Ok(()),
+        };
+        result.and_then(|()| self.flush()).map_err(|e| {
+            eprintln!("Error while writing output: {}", e);
+            Failure::Io
+        })
+    }
+}
+
+impl Write for ProgramOutput {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            ProgramOutput::Raw(writer) => writer.write(buf),
+            ProgramOutput::Hex(writer) => writer.write(buf),
+            ProgramOutput::Escaped(writer) => writer.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            ProgramOutput::Raw(writer) => writer.flush(),
+            ProgramOutput::Hex(writer) => writer.flush(),
+            ProgramOutput::Escaped(writer) => writer.flush(),
+        }
+    }
+}
+
+/// Writes to the file given by `--output`, or to stdout.
+fn write_output(matches: &ArgMatches, data: &[u8]) -> Result<(), Failure> {
+    let result = match matches.get_one::<String>("output") {
+        Some(output) => fs::write(output, data),
+        None => io::stdout().write_all(data),
+    };
+    result.map_err(|e| {
+        eprintln!("Error while writing output: {}", e);
+        Failure::Io
+    })
+}
@@ -0,0 +1,113 @@
+use crate::optimizer::transfer_factor;
+use crate::structs::Op::*;
+use crate::structs::{Op, OpStream};
+
+/// Rewrites the program to be as short as possible as Brainfuck text while
+/// writing the same output. On top of [`OpStream::optimize`], this removes
+/// loops that can't be entered, because the tape is still blank or they
+/// directly follow another loop, and changes at the end that nothing reads
+/// anymore. Format the result with `{}` to get the text.
+///
+/// Like the optimizer, this doesn't keep moves that cancel each other out, so
+/// programs that only fail by leaving the tape may not fail anymore.
+pub fn minify(ops: &[Op]) -> OpStream {
+    let mut opstream = OpStream { ops: ops.to_vec() };
+    opstream.optimize();
+    remove_dead(&mut opstream.ops, true);
+    // what was around removed loops may combine now
+    opstream.optimize();
+    // changes at the end, but not loops that may not end
+    while let Some(op) = opstream.ops.last() {
+        match op {
+            Add(_) | Mov(_) => {}
+            Transfer(d, _) if transfer_factor(*d).is_some() => {}
+            _ => break,
+        }
+        opstream.ops.pop();
+    }
+    opstream
+}
+
+/// Removes loops that never run, given whether the tape is `blank`.
+fn remove_dead(ops: &mut Vec<Op>, mut blank: bool) {
+    // whether the current cell is zero
+    let mut zero = blank;
+    ops.retain_mut(|op| {
+        match op {
+            Loop(_) | Transfer(_, _) if zero => return false,
+            Loop(stream) => {
+                remove_dead(&mut stream.ops, false);
+                zero = true;
+            }
+            Transfer(_, map) => {
+                map.retain(|&(_, v)| v != 0);
+                zero = true;
+            }
+            Add(_) | In => {
+                blank = false;
+                zero = false;
+            }
+            Mov(_) => zero = blank,
+            Out => {}
+        }
+        true
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::minify;
+
+    use crate::parser::parse;
+    use crate::runner::{Error, State};
+    use crate::strategies::{opstream, total_movement};
+    use crate::structs::Op;
+    use crate::structs::Op::*;
+
+    const MAX_STEPS: u64 = 10_000;
+
+    /// Output and result of a run.
+    fn observe(ops: &[Op], input: &[u8], start: usize) -> (Vec<u8>, Result<bool, Error>) {
+        let mut input = input;
+        let mut output = vec![];
+        let exit = {
+            let mut state = State::new(&mut input, &mut output);
+            state.set_max_steps(MAX_STEPS);
+            state.run(&[Mov(start as isize)]).unwrap();
+            state.run(ops)
+        };
+        (output, exit)
+    }
+
+    proptest! {
+        #[test]
+        fn test_minify_keeps_output(
+            opstream in opstream(true),
+            input in prop::collection::vec(any::<u8>(), 0..16),
+        ) {
+            let minified = minify(opstream.get());
+            let text = minified.to_string();
+            prop_assert!(text.len() <= opstream.to_string().len());
+            prop_assert_eq!(parse(text.as_bytes()).map(|ops| minify(&ops)), Ok(minified.clone()));
+
+            // start far enough to the right that no run can leave the tape
+            let start = (MAX_STEPS as usize + 1) * total_movement(opstream.get());
+            let expected = observe(opstream.get(), &input, start);
+            // the minified program takes fewer steps, so it may still finish
+            if expected.1 != Err(Error::OutOfSteps) {
+                let actual = observe(minified.get(), &input, start);
+                prop_assert_eq!(expected, actual, "minified: {}", text);
+            }
+        }
+    }
+
+    #[test]
+    fn test_minify() {
+        let ops =
+            parse(b"Comment [dead] >[-]< +-+ [[-]>[x]] [>+<->-<] >. <<>> [[-]] ,[.,] +>+ [-]")
+                .unwrap();
+        assert_eq!(minify(&ops).to_string(), "+[[-]>[]]>.[[-]],[.,]");
+    }
+}
@@ -0,0 +1,112 @@
+#![cfg(feature = "llvm")]
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Counts the files written, to give each one a temporary name of its own.
+static WRITES: AtomicU64 = AtomicU64::new(0);
+
+/// Compiled programs in a directory, one file per key, named after its hash.
+/// The files start with the whole key, so that keys with the same hash can't
+/// be mixed up.
+pub(crate) struct ObjectCache {
+    dir: PathBuf,
+}
+
+impl ObjectCache {
+    pub(crate) fn new(dir: &Path) -> ObjectCache {
+        ObjectCache {
+            dir: dir.to_path_buf(),
+        }
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{:016x}.o", fnv1a(key.as_bytes())))
+    }
+
+    /// The object stored for `key`, if any.
+    pub(crate) fn get(&self, key: &str) -> Option<Vec<u8>> {
+        let mut contents = fs::read(self.path(key)).ok()?;
+        let end = contents.iter().position(|&byte| byte == 0)?;
+        if &contents[..end] != key.as_bytes() {
+            return None;
+        }
+        Some(contents.split_off(end + 1))
+    }
+
+    /// Stores the object for `key`. The file is written under a temporary
+    /// name unique to the process and the write first, so that concurrent
+    /// runs, also on other threads, never read half of it.
+    pub(crate) fn put(&self, key: &str, object: &[u8]) -> io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        let path = self.path(key);
+        let write = WRITES.fetch_add(1, Ordering::Relaxed);
+        let temporary = path.with_extension(format!("{}.{}.tmp", process::id(), write));
+        let result = fs::write(&temporary, [key.as_bytes(), &[0], object].concat())
+            .and_then(|()| fs::rename(&temporary, &path));
+        if result.is_err() {
+            let _ = fs::remove_file(&temporary);
+        }
+        result
+    }
+}
+
+/// The 64-bit FNV-1a hash. Unlike the hashers of the standard library, it
+/// stays the same across Rust releases, so the files are found again.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::thread;
+
+    use super::{fnv1a, ObjectCache};
+
+    #[test]
+    fn test_fnv1a() {
+        assert_eq!(fnv1a(b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(fnv1a(b"a"), 0xaf63_dc4c_8601_ec8c);
+        assert_eq!(fnv1a(b"foobar"), 0x8594_4171_f739_67e8);
+    }
+
+    #[test]
+    fn test_object_cache() {
+        let dir = std::env::temp_dir().join(format!("brain_rust_cache_{}", std::process::id()));
+        let cache = ObjectCache::new(&dir);
+        assert_eq!(cache.get("+."), None);
+        cache.put("+.", b"\x7fELF\0object").unwrap();
+        cache.put("-.", b"other").unwrap();
+        assert_eq!(cache.get("+.").as_deref(), Some(&b"\x7fELF\0object"[..]));
+        assert_eq!(cache.get("-.").as_deref(), Some(&b"other"[..]));
+
+        // a file with the right name but for another key is ignored
+        fs::rename(cache.path("-."), cache.path("+.")).unwrap();
+        assert_eq!(cache.get("+."), None);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_object_cache_concurrent_writes() {
+        let dir = std::env::temp_dir().join(format!(
+            "brain_rust_cache_concurrent_{}",
+            std::process::id()
+        ));
+        let cache = ObjectCache::new(&dir);
+        let object = vec![0x55; 1 << 20];
+        thread::scope(|scope| {
+            for _ in 0..8 {
+                scope.spawn(|| cache.put("+.", &object).unwrap());
+            }
+        });
+        assert_eq!(cache.get("+."), Some(object));
+        // only the object is left, no temporary files
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -4,6 +4,8 @@ use crate::structs::Op::*;
 use crate::structs::{Op, OpStream};
 
 impl OpStream {
+    /// Merges consecutive additions and moves, drops operations without
+    /// effect and replaces simple loops by [`Transfer`] operations.
     pub fn optimize(&mut self) {
         let mut i = 0;
         while i < self.ops.len() {
@@ -12,7 +14,7 @@ impl OpStream {
                     self.ops[i] = Add(a.wrapping_add(b));
                     self.ops.remove(i + 1);
                 }
-                [Mov(a), Mov(b), ..] => {
+                [Mov(a), Mov(b), ..] if a.checked_add(b).is_some() => {
                     self.ops[i] = Mov(a + b);
                     self.ops.remove(i + 1);
                 }
@@ -42,7 +44,7 @@ impl OpStream {
                     map.insert(rel_index, map.get(&rel_index).unwrap_or(&0).wrapping_add(x));
                 }
                 Mov(x) => {
-                    rel_index += x;
+                    rel_index = rel_index.checked_add(x)?;
                 }
                 _ => {
                     return None;
@@ -57,10 +59,84 @@ impl OpStream {
     }
 }
 
+/// The number of iterations of a [`Transfer`] with step `d`, as a factor of
+/// the initial value of its cell. Even steps have no such factor and may
+/// stall.
+pub(crate) fn transfer_factor(d: u8) -> Option<u8> {
+    (0..=255u8)
+        .find(|&x| d.wrapping_mul(x) == 1)
+        .map(|inverse| inverse.wrapping_neg())
+}
+
 #[cfg(test)]
 mod tests {
+    use proptest::prelude::*;
+
+    use super::transfer_factor;
+
+    use crate::runner::{Error, State};
+    use crate::strategies::{opstream, total_movement};
     use crate::structs::Op::*;
-    use crate::structs::OpStream;
+    use crate::structs::{Op, OpStream};
+
+    const MAX_STEPS: u64 = 10_000;
+
+    /// Output, tape without trailing zeros, pointer and result of a run.
+    type Observation = (Vec<u8>, Vec<u8>, usize, Result<bool, Error>);
+
+    fn observe(ops: &[Op], input: &[u8], start: usize) -> Observation {
+        let mut input = input;
+        let mut output = vec![];
+        let (mut memory, index, exit) = {
+            let mut state = State::new(&mut input, &mut output);
+            state.set_max_steps(MAX_STEPS);
+            state.run(&[Mov(start as isize)]).unwrap();
+            let exit = state.run(ops);
+            (state.memory().to_vec(), state.index(), exit)
+        };
+        while memory.last() == Some(&0) {
+            memory.pop();
+        }
+        (output, memory, index, exit)
+    }
+
+    fn check_optimize(opstream: &OpStream, input: &[u8]) -> Result<(), TestCaseError> {
+        // start far enough to the right that no run can leave the tape
+        let start = (MAX_STEPS as usize + 1) * total_movement(opstream.get());
+        let mut optimized = opstream.clone();
+        optimized.optimize();
+        let expected = observe(opstream.get(), input, start);
+        // the optimized program takes fewer steps, so it may still finish
+        if expected.3 == Err(Error::OutOfSteps) {
+            return Ok(());
+        }
+        let actual = observe(optimized.get(), input, start);
+        prop_assert_eq!(expected, actual, "optimized: {:?}", optimized);
+        Ok(())
+    }
+
+    proptest! {
+        #[test]
+        fn test_opstream_optimize_keeps_behaviour(opstream in opstream(false)) {
+            check_optimize(&opstream, &[])?;
+        }
+
+        #[test]
+        fn test_opstream_optimize_keeps_behaviour_with_io(
+            opstream in opstream(true),
+            input in prop::collection::vec(any::<u8>(), 0..16),
+        ) {
+            check_optimize(&opstream, &input)?;
+        }
+    }
+
+    #[test]
+    fn test_transfer_factor() {
+        assert_eq!(Some(1), transfer_factor(0xff));
+        assert_eq!(Some(0xff), transfer_factor(1));
+        assert_eq!(Some(0x55), transfer_factor(3));
+        assert_eq!(None, transfer_factor(2));
+    }
 
     #[test]
     fn test_opstream_optimize() {
@@ -90,6 +166,23 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_opstream_optimize_overflowing_moves() {
+        let mut opstream = OpStream {
+            ops: vec![
+                Mov(isize::MAX),
+                Mov(1),
+                Loop(OpStream {
+                    ops: vec![Add(0xff), Mov(isize::MAX), Add(1), Mov(isize::MAX)],
+                }),
+            ],
+        };
+        let expected = opstream.clone();
+        opstream.optimize();
+
+        assert_eq!(opstream, expected);
+    }
+
     #[test]
     fn test_opstream_optimize_transfer() {
         let mut opstream = OpStream {
@@ -0,0 +1,241 @@
+use std::io::{self, Write};
+
+const BYTES_PER_LINE: usize = 16;
+const BUFFER_SIZE: usize = 8192;
+
+/// When the runners write out the program's buffered output. Output is also
+/// written whenever the buffer is full.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Flush {
+    /// After every line break, before reading input and at the end of a run.
+    #[default]
+    Newline,
+    /// Before reading input and at the end of a run.
+    Input,
+    /// Only at the end of a run.
+    Exit,
+}
+
+/// The program's output as buffered by the runners, following a [`Flush`]
+/// policy.
+pub(crate) struct OutputBuffer<'a, W: Write> {
+    output: &'a mut W,
+    buffer: Vec<u8>,
+    pub(crate) flush: Flush,
+}
+
+impl<'a, W: Write> OutputBuffer<'a, W> {
+    pub(crate) fn new(output: &'a mut W) -> OutputBuffer<'a, W> {
+        OutputBuffer {
+            output,
+            buffer: Vec::with_capacity(BUFFER_SIZE),
+            flush: Flush::default(),
+        }
+    }
+
+    pub(crate) fn put(&mut self, byte: u8) -> io::Result<()> {
+        self.buffer.push(byte);
+        if self.buffer.len() == BUFFER_SIZE || (byte == b'\n' && self.flush == Flush::Newline) {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Writes out the output so far if the policy says so, so that prompts
+    /// show up before the program waits for input.
+    pub(crate) fn before_input(&mut self) -> io::Result<()> {
+        if self.flush != Flush::Exit {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Writes out the buffered output. The output is dropped if that fails,
+    /// so that it isn't written twice when trying again.
+    pub(crate) fn flush(&mut self) -> io::Result<()> {
+        let result = self.output.write_all(&self.buffer);
+        self.buffer.clear();
+        result?;
+        self.output.flush()
+    }
+}
+
+/// Writes everything as a hex dump like `xxd`'s: lines of 16 bytes with their
+/// offset and the printable ones as ASCII. An incomplete last line is written
+/// when the writer is dropped or [`finish`](HexWriter::finish)ed.
+pub struct HexWriter<W: Write> {
+    inner: W,
+    offset: usize,
+    line: Vec<u8>,
+}
+
+impl<W: Write> HexWriter<W> {
+    /// Creates a hex dump of everything written into `inner`.
+    pub fn new(inner: W) -> HexWriter<W> {
+        HexWriter {
+            inner,
+            offset: 0,
+            line: Vec::with_capacity(BYTES_PER_LINE),
+        }
+    }
+
+    /// Writes the incomplete last line, if any.
+    pub fn finish(&mut self) -> io::Result<()> {
+        if self.line.is_empty() {
+            return Ok(());
+        }
+        let mut text = format!("{:08x}: ", self.offset);
+        for (i, byte) in self.line.iter().enumerate() {
+            if i == BYTES_PER_LINE / 2 {
+                text.push(' ');
+            }
+            text.push_str(&format!(" {:02x}", byte));
+        }
+        // align the ASCII column of incomplete lines
+        let missing = BYTES_PER_LINE - self.line.len();
+        text.push_str(&" ".repeat(missing * 3 + usize::from(self.line.len() <= 8)));
+        text.push_str("  ");
+        text.extend(self.line.iter().map(|&byte| match byte {
+            0x20..0x7f => byte as char,
+            _ => '.',
+        }));
+        text.push('\n');
+        self.inner.write_all(text.as_bytes())?;
+        self.offset += self.line.len();
+        self.line.clear();
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for HexWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        for &byte in buf {
+            self.line.push(byte);
+            if self.line.len() == BYTES_PER_LINE {
+                self.finish()?;
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<W: Write> Drop for HexWriter<W> {
+    fn drop(&mut self) {
+        let _ = self.finish().and_then(|()| self.inner.flush());
+    }
+}
+
+/// Writes text as it is, but other bytes as escape sequences: `\\`, `\r`, `\0`
+/// and `\xNN`. Line breaks and tabs are kept.
+pub struct EscapeWriter<W: Write> {
+    inner: W,
+}
+
+impl<W: Write> EscapeWriter<W> {
+    /// Escapes everything written into `inner`.
+    pub fn new(inner: W) -> EscapeWriter<W> {
+        EscapeWriter { inner }
+    }
+}
+
+impl<W: Write> Write for EscapeWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut text = Vec::with_capacity(buf.len());
+        for &byte in buf {
+            match byte {
+                b'\\' => text.extend(b"\\\\"),
+                b'\r' => text.extend(b"\\r"),
+                0 => text.extend(b"\\0"),
+                b'\n' | b'\t' | 0x20..0x7f => text.push(byte),
+                _ => text.extend(format!("\\x{:02x}", byte).bytes()),
+            }
+        }
+        self.inner.write_all(&text)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::{EscapeWriter, Flush, HexWriter, OutputBuffer};
+
+    fn buffered(flush: Flush, text: &[u8]) -> Vec<usize> {
+        let mut output = vec![];
+        let mut buffer = OutputBuffer::new(&mut output);
+        buffer.flush = flush;
+        // how much was written out after each byte
+        let mut written = vec![];
+        for &byte in text {
+            if byte == b',' {
+                buffer.before_input().unwrap();
+            } else {
+                buffer.put(byte).unwrap();
+            }
+            written.push(buffer.output.len());
+        }
+        buffer.flush().unwrap();
+        written.push(buffer.output.len());
+        written
+    }
+
+    #[test]
+    fn test_output_buffer() {
+        assert_eq!(buffered(Flush::Newline, b"a\nb,c"), [0, 2, 2, 3, 3, 4]);
+        assert_eq!(buffered(Flush::Input, b"a\nb,c"), [0, 0, 0, 3, 3, 4]);
+        assert_eq!(buffered(Flush::Exit, b"a\nb,c"), [0, 0, 0, 0, 0, 4]);
+    }
+
+    #[test]
+    fn test_output_buffer_full() {
+        let mut output = vec![];
+        let mut buffer = OutputBuffer::new(&mut output);
+        buffer.flush = Flush::Exit;
+        for _ in 0..super::BUFFER_SIZE {
+            buffer.put(b'a').unwrap();
+        }
+        assert_eq!(buffer.output.len(), super::BUFFER_SIZE);
+    }
+
+    #[test]
+    fn test_output_buffer_error() {
+        let mut output = [0u8; 1];
+        let mut writer = &mut output[..];
+        let mut buffer = OutputBuffer::new(&mut writer);
+        buffer.put(b'a').unwrap();
+        buffer.put(b'\n').unwrap_err();
+        assert!(buffer.buffer.is_empty());
+    }
+
+    #[test]
+    fn test_hex_writer() {
+        let mut output = vec![];
+        {
+            let mut writer = HexWriter::new(&mut output);
+            writer.write_all(b"Hello, World!\n\x00\xff").unwrap();
+            writer.write_all(b"abc").unwrap();
+        }
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "00000000:  48 65 6c 6c 6f 2c 20 57  6f 72 6c 64 21 0a 00 ff  Hello, World!...\n\
+             00000010:  61 62 63                                          abc\n"
+        );
+    }
+
+    #[test]
+    fn test_escape_writer() {
+        let mut output = vec![];
+        let mut writer = EscapeWriter::new(&mut output);
+        writer.write_all(b"a\\b\n\t\r\x00\x1b\xff").unwrap();
+        assert_eq!(output, b"a\\\\b\n\t\\r\\0\\x1b\\xff");
+    }
+}
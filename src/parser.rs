@@ -15,29 +15,48 @@ impl fmt::Display for Position {
     }
 }
 
+/// A piece of Brainfuck source as kept by [`parse_lossless`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Node {
+    /// A command other than a bracket.
+    Command(u8),
+    /// Consecutive characters that are not commands, such as comments and
+    /// whitespace.
+    Trivia(Vec<u8>),
+    /// A loop with everything between its brackets.
+    Loop(Vec<Node>),
+}
+
+/// Parses Brainfuck source text, ignoring all characters that are not
+/// commands. Fails with a message pointing at the first unbalanced bracket.
 pub fn parse(text: &[u8]) -> Result<Vec<Op>, String> {
+    Ok(lower(&parse_lossless(text)?))
+}
+
+/// Parses Brainfuck source text into a tree that keeps every character, so
+/// that writing out the nodes in order gives back the text. Fails like
+/// [`parse`].
+pub fn parse_lossless(text: &[u8]) -> Result<Vec<Node>, String> {
     let mut stack = vec![];
     let mut current = vec![];
     let mut position = Position { line: 1, column: 1 };
 
     for c in text {
         match *c {
-            b'+' => current.push(Add(0x01)),
-            b'-' => current.push(Add(0xff)),
-            b'>' => current.push(Mov(1)),
-            b'<' => current.push(Mov(-1)),
-            b'.' => current.push(Out),
-            b',' => current.push(In),
+            b'+' | b'-' | b'>' | b'<' | b'.' | b',' => current.push(Node::Command(*c)),
             b'[' => {
                 stack.push(current);
                 current = vec![];
             }
             b']' => {
-                let opstream = OpStream { ops: current };
+                let nodes = current;
                 current = stack.pop().ok_or(format!("Stray ] at {}", position))?;
-                current.push(Loop(opstream));
+                current.push(Node::Loop(nodes));
             }
-            _ => {}
+            _ => match current.last_mut() {
+                Some(Node::Trivia(trivia)) => trivia.push(*c),
+                _ => current.push(Node::Trivia(vec![*c])),
+            },
         }
         if *c == b'\n' {
             position.line += 1;
@@ -54,13 +73,55 @@ pub fn parse(text: &[u8]) -> Result<Vec<Op>, String> {
     }
 }
 
+fn lower(nodes: &[Node]) -> Vec<Op> {
+    nodes
+        .iter()
+        .filter_map(|node| match node {
+            Node::Command(b'+') => Some(Add(0x01)),
+            Node::Command(b'-') => Some(Add(0xff)),
+            Node::Command(b'>') => Some(Mov(1)),
+            Node::Command(b'<') => Some(Mov(-1)),
+            Node::Command(b'.') => Some(Out),
+            Node::Command(b',') => Some(In),
+            Node::Loop(nodes) => Some(Loop(OpStream { ops: lower(nodes) })),
+            _ => None,
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
-    use super::parse;
+    use proptest::prelude::*;
 
+    use super::{parse, parse_lossless, Node};
+
+    use crate::strategies::source;
     use crate::structs::Op::*;
     use crate::structs::OpStream;
 
+    fn write(nodes: &[Node], out: &mut Vec<u8>) {
+        for node in nodes {
+            match node {
+                Node::Command(c) => out.push(*c),
+                Node::Trivia(trivia) => out.extend(trivia),
+                Node::Loop(nodes) => {
+                    out.push(b'[');
+                    write(nodes, out);
+                    out.push(b']');
+                }
+            }
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn test_parse_lossless_keeps_text(text in source()) {
+            let mut written = vec![];
+            write(&parse_lossless(text.as_bytes()).unwrap(), &mut written);
+            prop_assert_eq!(text.as_bytes(), &written[..]);
+        }
+    }
+
     #[test]
     fn test_parse() {
         let input = b"+>-[+.,]+<";
@@ -95,4 +156,20 @@ mod tests {
         let input = include_bytes!("../test_cases/incomplete.bf");
         assert_eq!(parse(input), Err("Missing ] at line 4, column 1".into()));
     }
+
+    #[test]
+    fn test_parse_lossless() {
+        assert_eq!(
+            parse_lossless(b"+ a\n[-]"),
+            Ok(vec![
+                Node::Command(b'+'),
+                Node::Trivia(b" a\n".to_vec()),
+                Node::Loop(vec![Node::Command(b'-')]),
+            ])
+        );
+        assert_eq!(
+            parse_lossless(include_bytes!("../test_cases/stray.bf")),
+            Err("Stray ] at line 3, column 3".into())
+        );
+    }
 }
@@ -0,0 +1,136 @@
+use std::fmt::{self, Display, Formatter, Write};
+
+use crate::structs::Op::*;
+use crate::structs::{Op, OpStream};
+
+impl Display for Op {
+    /// Writes the operation as Brainfuck, with a [`Transfer`] as the loop it
+    /// replaced.
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            Add(i) if i < 0x80 => repeat(f, '+', i.into()),
+            Add(i) => repeat(f, '-', i.wrapping_neg().into()),
+            Mov(n) if n < 0 => repeat(f, '<', n.unsigned_abs()),
+            Mov(n) => repeat(f, '>', n.unsigned_abs()),
+            In => f.write_char(','),
+            Out => f.write_char('.'),
+            Loop(ref stream) => write!(f, "[{}]", stream),
+            Transfer(d, ref map) => {
+                write!(f, "[{}", Add(d))?;
+                let mut position = 0;
+                for &(k, v) in map {
+                    write!(f, "{}{}", Mov(k - position), Add(v))?;
+                    position = k;
+                }
+                write!(f, "{}]", Mov(-position))
+            }
+        }
+    }
+}
+
+impl Display for OpStream {
+    /// Writes the operations as Brainfuck without any whitespace. With the
+    /// alternate flag (`{:#}`), loops that contain loops span several lines
+    /// and their bodies are indented by four spaces per level, or by the
+    /// width if one is given (`{:#2}`).
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        if f.alternate() {
+            let indent = " ".repeat(f.width().unwrap_or(4));
+            write_lines(f, self.get(), &indent, 0)
+        } else {
+            self.ops.iter().try_for_each(|op| write!(f, "{}", op))
+        }
+    }
+}
+
+fn repeat(f: &mut Formatter, c: char, count: usize) -> fmt::Result {
+    (0..count).try_for_each(|_| f.write_char(c))
+}
+
+fn write_lines(f: &mut Formatter, ops: &[Op], indent: &str, depth: usize) -> fmt::Result {
+    let mut line = String::new();
+    for op in ops {
+        match op {
+            Loop(stream) if stream.get().iter().any(|op| matches!(op, Loop(_))) => {
+                write_line(f, &mut line, indent, depth)?;
+                line.push('[');
+                write_line(f, &mut line, indent, depth)?;
+                write_lines(f, stream.get(), indent, depth + 1)?;
+                line.push(']');
+                write_line(f, &mut line, indent, depth)?;
+            }
+            op => write!(line, "{}", op)?,
+        }
+    }
+    write_line(f, &mut line, indent, depth)
+}
+
+/// Writes and clears the line unless it's empty.
+fn write_line(f: &mut Formatter, line: &mut String, indent: &str, depth: usize) -> fmt::Result {
+    if line.is_empty() {
+        return Ok(());
+    }
+    for _ in 0..depth {
+        f.write_str(indent)?;
+    }
+    writeln!(f, "{}", line)?;
+    line.clear();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use crate::parser::parse;
+    use crate::strategies::opstream;
+    use crate::structs::Op::*;
+    use crate::structs::OpStream;
+
+    proptest! {
+        #[test]
+        fn test_display_parses_back(opstream in opstream(true), alternate: bool) {
+            let text = if alternate {
+                format!("{:#}", opstream)
+            } else {
+                format!("{}", opstream)
+            };
+            let mut expected = opstream;
+            expected.optimize();
+            let mut actual = OpStream { ops: parse(text.as_bytes()).unwrap() };
+            actual.optimize();
+            prop_assert_eq!(expected, actual, "text: {}", text);
+        }
+    }
+
+    #[test]
+    fn test_display() {
+        let stream = OpStream {
+            ops: vec![
+                Add(3),
+                Mov(-2),
+                Loop(OpStream {
+                    ops: vec![Add(0xfe), Out, In],
+                }),
+                Transfer(0xff, vec![(-1, 1), (2, 3)]),
+                Transfer(2, vec![]),
+            ],
+        };
+        assert_eq!("+++<<[--.,][-<+>>>+++<<][++]", stream.to_string());
+    }
+
+    #[test]
+    fn test_display_indented() {
+        let stream = OpStream {
+            ops: parse(b"++[>++[>+<-]<-]>>.[[-]<]").unwrap(),
+        };
+        assert_eq!(
+            format!("{:#}", stream),
+            "++\n[\n    >++[>+<-]<-\n]\n>>.\n[\n    [-]<\n]\n"
+        );
+        assert_eq!(
+            format!("{:#2}", stream),
+            "++\n[\n  >++[>+<-]<-\n]\n>>.\n[\n  [-]<\n]\n"
+        );
+    }
+}
@@ -1,98 +1,409 @@
-use std::io::{Read, Write};
+use std::fmt;
+use std::io::{self, Read, Write};
 use std::ops::{Index, IndexMut};
-use std::thread;
+use std::sync::atomic::{AtomicBool, Ordering};
 
+use crate::bytecode::{Bytecode, Instr};
+use crate::output::{Flush, OutputBuffer};
 use crate::structs::Op;
 use crate::structs::Op::*;
 
+/// Reasons for the interpreter to stop a program early.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// Reading the input failed.
+    Input(io::ErrorKind),
+    /// Writing the output failed, e.g. because the pipe was closed.
+    Output(io::ErrorKind),
+    /// The step budget is used up.
+    OutOfSteps,
+    /// The pointer moved off either end of the tape.
+    PointerOutOfRange,
+    /// The program is stuck in a loop that never ends. Only reported without
+    /// a step budget, which such a loop uses up instead.
+    NonTermination,
+    /// The run was cancelled with the flag given to [`State::set_cancel`].
+    Cancelled,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Input(kind) => write!(f, "Can't read input: {}", kind),
+            Error::Output(kind) => write!(f, "Can't write output: {}", kind),
+            Error::OutOfSteps => write!(f, "Step budget exhausted"),
+            Error::PointerOutOfRange => write!(f, "Pointer moved off the tape"),
+            Error::NonTermination => write!(f, "Loop never ends"),
+            Error::Cancelled => write!(f, "Cancelled"),
+        }
+    }
+}
+
+/// What reading does at the end of the input.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Eof {
+    /// Stops the program.
+    #[default]
+    Stop,
+    /// Leaves the current cell unchanged.
+    Unchanged,
+    /// Sets the current cell to 0.
+    Zero,
+    /// Sets the current cell to 255, C's `EOF` as a byte.
+    MinusOne,
+}
+
+impl Eof {
+    /// Updates the current cell at the end of the input. Returns `false` if
+    /// the program stops instead.
+    pub(crate) fn apply(self, cell: &mut u8) -> bool {
+        match self {
+            Eof::Stop => return false,
+            Eof::Unchanged => {}
+            Eof::Zero => *cell = 0,
+            Eof::MinusOne => *cell = 0xff,
+        }
+        true
+    }
+}
+
+/// Settings of a program run: limits of the interpreter, all unset by
+/// default, and the behaviour at the end of the input.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Config {
+    /// See [`State::set_max_steps`].
+    pub max_steps: Option<u64>,
+    /// See [`State::set_tape_size`].
+    pub tape_size: Option<usize>,
+    /// See [`State::set_eof`].
+    pub eof: Eof,
+}
+
+/// The interpreter: a tape, a pointer into it and the program's input and
+/// output.
 pub struct State<'a, R: Read, W: Write> {
     index: usize,
     memory: Vec<u8>,
     input: &'a mut R,
-    output: &'a mut W,
+    output: OutputBuffer<'a, W>,
+    steps_left: Option<u64>,
+    tape_size: usize,
+    eof: Eof,
+    cancel: Option<&'a AtomicBool>,
 }
 
 impl<'a, R: Read, W: Write> State<'a, R, W> {
+    /// Creates an interpreter with an empty tape and no limits.
     pub fn new<'b>(input: &'b mut R, output: &'b mut W) -> State<'b, R, W> {
         State {
             index: 0,
             memory: vec![],
             input,
-            output,
+            output: OutputBuffer::new(output),
+            steps_left: None,
+            tape_size: usize::MAX,
+            eof: Eof::Stop,
+            cancel: None,
         }
     }
 
-    fn rel_index(&self, relative: isize) -> usize {
-        (self.index as isize + relative) as usize
+    /// Creates an interpreter with an empty tape and the settings of
+    /// `config`.
+    pub fn with_config<'b>(
+        input: &'b mut R,
+        output: &'b mut W,
+        config: &Config,
+    ) -> State<'b, R, W> {
+        let mut state = State::new(input, output);
+        if let Some(max_steps) = config.max_steps {
+            state.set_max_steps(max_steps);
+        }
+        if let Some(tape_size) = config.tape_size {
+            state.set_tape_size(tape_size);
+        }
+        state.set_eof(config.eof);
+        state
     }
 
-    fn step(&mut self, op: &Op) -> bool {
+    /// Limits the number of steps of all following runs together. Loops count
+    /// one step per iteration in addition to the steps of their body.
+    pub fn set_max_steps(&mut self, max_steps: u64) {
+        self.steps_left = Some(max_steps);
+    }
+
+    /// Limits the tape to cells `0..tape_size`. Without a limit, the tape only
+    /// ends at the left.
+    ///
+    /// # Panics
+    ///
+    /// If `tape_size` is 0, as the pointer starts on cell 0.
+    pub fn set_tape_size(&mut self, tape_size: usize) {
+        assert!(tape_size > 0, "The tape needs at least one cell");
+        self.tape_size = tape_size;
+    }
+
+    /// Sets what reading does at the end of the input, by default stopping
+    /// the program.
+    pub fn set_eof(&mut self, eof: Eof) {
+        self.eof = eof;
+    }
+
+    /// Sets when buffered output is written, by default after every line
+    /// break. It is always written before a run returns.
+    pub fn set_flush(&mut self, flush: Flush) {
+        self.output.flush = flush;
+    }
+
+    /// Stops runs with [`Error::Cancelled`] once `cancel` is set, e.g. by
+    /// another thread when the program takes too long.
+    pub fn set_cancel(&mut self, cancel: &'a AtomicBool) {
+        self.cancel = Some(cancel);
+    }
+
+    /// The position of the pointer.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// The tape as far as it has been written to.
+    pub fn memory(&self) -> &[u8] {
+        &self.memory[..]
+    }
+
+    /// The current cell. Unlike `self[0]`, this doesn't check the pointer
+    /// again, as moves only ever leave it on the tape.
+    fn cell(&self) -> u8 {
+        self.memory.get(self.index).copied().unwrap_or(0)
+    }
+
+    /// Cell `idx` of the tape, which grows to include it.
+    fn cell_mut(&mut self, idx: usize) -> &mut u8 {
+        if idx >= self.memory.len() {
+            let len = idx.saturating_mul(2).saturating_add(1).min(self.tape_size);
+            self.memory.resize(len, 0);
+        }
+        &mut self.memory[idx]
+    }
+
+    fn rel_index(&self, relative: isize) -> Result<usize, Error> {
+        self.index
+            .checked_add_signed(relative)
+            .filter(|&index| index < self.tape_size)
+            .ok_or(Error::PointerOutOfRange)
+    }
+
+    fn tick(&mut self) -> Result<(), Error> {
+        if self
+            .cancel
+            .is_some_and(|cancel| cancel.load(Ordering::Relaxed))
+        {
+            return Err(Error::Cancelled);
+        }
+        match self.steps_left {
+            Some(0) => Err(Error::OutOfSteps),
+            Some(ref mut steps) => {
+                *steps -= 1;
+                Ok(())
+            }
+            None => Ok(()),
+        }
+    }
+
+    fn step(&mut self, op: &Op) -> Result<bool, Error> {
+        self.tick()?;
         match *op {
             Add(i) => {
                 self[0] = self[0].wrapping_add(i);
             }
             Mov(n) => {
-                self.index = self.rel_index(n);
+                self.index = self.rel_index(n)?;
             }
             In => {
-                let mut c = [0u8];
-                if self.input.read(&mut c).unwrap() == 0 {
-                    return false;
-                }
-                self[0] = c[0];
+                return self.read();
             }
             Out => {
-                self.output.write_all(&[self[0]]).unwrap();
+                self.write()?;
             }
             Loop(ref ops) => {
+                if ops.get().is_empty() && self[0] != 0 && self.steps_left.is_none() {
+                    return Err(Error::NonTermination);
+                }
                 while self[0] != 0 {
-                    if !self.run(ops.get()) {
-                        return false;
+                    if !self.run_ops(ops.get())? {
+                        return Ok(false);
                     }
+                    self.tick()?;
                 }
             }
             Transfer(d, ref map) => {
-                if self[0] == 0 {
-                    return true;
-                }
+                self.transfer(d, map)?;
+            }
+        }
+        Ok(true)
+    }
 
-                let mut v0 = self[0];
-                let mut iterations = 0;
+    fn read(&mut self) -> Result<bool, Error> {
+        self.output.before_input()?;
+        let mut c = [0u8];
+        let read = self.input.read(&mut c);
+        if read.map_err(|e| Error::Input(e.kind()))? == 0 {
+            let eof = self.eof;
+            return Ok(eof.apply(&mut self[0]));
+        }
+        self[0] = c[0];
+        Ok(true)
+    }
 
-                while v0 != 0 {
-                    v0 = v0.wrapping_add(d);
-                    if v0 == self[0] {
-                        // stalled: the current transfer will never complete
-                        loop {
-                            thread::park()
-                        }
-                    }
-                    iterations += 1
-                }
+    fn write(&mut self) -> Result<(), Error> {
+        let cell = self[0];
+        Ok(self.output.put(cell)?)
+    }
+
+    fn transfer(&mut self, d: u8, map: &[(isize, u8)]) -> Result<(), Error> {
+        if self[0] == 0 {
+            return Ok(());
+        }
+
+        let mut v0 = self[0];
+        let mut iterations = 0;
 
-                self[0] = 0;
-                for &(k, v) in map {
-                    self[k] = self[k].wrapping_add(v.wrapping_mul(iterations));
+        while v0 != 0 {
+            v0 = v0.wrapping_add(d);
+            if v0 == self[0] {
+                // stalled: the current transfer will never complete
+                if self.steps_left.is_some() {
+                    return Err(Error::OutOfSteps);
                 }
+                return Err(Error::NonTermination);
             }
+            iterations += 1
         }
-        true
+
+        for &(k, _) in map {
+            self.rel_index(k)?;
+        }
+
+        self[0] = 0;
+        for &(k, v) in map {
+            self[k] = self[k].wrapping_add(v.wrapping_mul(iterations));
+        }
+        Ok(())
+    }
+
+    /// Runs the operations on the current tape. Returns `Ok(false)` if the
+    /// program stopped at the end of the input.
+    pub fn run(&mut self, ops: &[Op]) -> Result<bool, Error> {
+        let result = self.run_ops(ops);
+        finish(result, self.output.flush())
     }
 
-    pub fn run(&mut self, ops: &[Op]) -> bool {
+    fn run_ops(&mut self, ops: &[Op]) -> Result<bool, Error> {
         for op in ops {
-            if !self.step(op) {
-                return false;
+            if !self.step(op)? {
+                return Ok(false);
             }
         }
-        true
+        Ok(true)
+    }
+
+    /// Runs compiled operations on the current tape, with the same results
+    /// and step counts as [`State::run`] on the operations they came from.
+    pub fn run_bytecode(&mut self, bytecode: &Bytecode) -> Result<bool, Error> {
+        // without a step budget or a cancel flag there is nothing to check
+        // between instructions
+        let result = if self.steps_left.is_none() && self.cancel.is_none() {
+            self.execute::<false>(bytecode, |_, _| {})
+        } else {
+            self.execute::<true>(bytecode, |_, _| {})
+        };
+        finish(result, self.output.flush())
+    }
+
+    /// Like [`State::run_bytecode`], but calls `trace` with the position of
+    /// each instruction and the state before executing it.
+    pub fn run_bytecode_traced(
+        &mut self,
+        bytecode: &Bytecode,
+        trace: impl FnMut(usize, &Self),
+    ) -> Result<bool, Error> {
+        let result = self.execute::<true>(bytecode, trace);
+        finish(result, self.output.flush())
+    }
+
+    /// Executes the instructions. Only if `CHECKED` is set, it calls `trace`,
+    /// counts steps and checks the cancel flag before each one.
+    fn execute<const CHECKED: bool>(
+        &mut self,
+        bytecode: &Bytecode,
+        mut trace: impl FnMut(usize, &Self),
+    ) -> Result<bool, Error> {
+        let code = bytecode.instructions();
+        let mut pc = 0;
+        while let Some(instr) = code.get(pc) {
+            if CHECKED {
+                trace(pc, self);
+                self.tick()?;
+            }
+            match *instr {
+                Instr::Add(i) => {
+                    let cell = self.cell_mut(self.index);
+                    *cell = cell.wrapping_add(i);
+                }
+                Instr::Mov(n) => {
+                    self.index = self.rel_index(n)?;
+                }
+                Instr::In => {
+                    if !self.read()? {
+                        return Ok(false);
+                    }
+                }
+                Instr::Out => {
+                    self.write()?;
+                }
+                Instr::JumpIfZero(target) => {
+                    if self.cell() == 0 {
+                        pc = target;
+                        continue;
+                    }
+                }
+                Instr::JumpIfNotZero(target) => {
+                    if self.cell() != 0 {
+                        // the end of an empty loop jumps to itself
+                        if target == pc && self.steps_left.is_none() {
+                            return Err(Error::NonTermination);
+                        }
+                        pc = target;
+                        continue;
+                    }
+                }
+                Instr::Transfer(d, ref map) => {
+                    self.transfer(d, map)?;
+                }
+            }
+            pc += 1;
+        }
+        Ok(true)
     }
 }
 
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Error {
+        Error::Output(error.kind())
+    }
+}
+
+/// The result of a run once the output is written out at its end. An earlier
+/// error takes precedence over one writing the output.
+pub(crate) fn finish(result: Result<bool, Error>, flushed: io::Result<()>) -> Result<bool, Error> {
+    let finished = result?;
+    flushed?;
+    Ok(finished)
+}
+
 impl<'a, R: Read, W: Write> Index<isize> for State<'a, R, W> {
     type Output = u8;
     fn index(&self, index: isize) -> &u8 {
-        let idx = self.rel_index(index);
+        let idx = self.rel_index(index).unwrap();
         if idx >= self.memory.len() {
             &0
         } else {
@@ -103,29 +414,35 @@ impl<'a, R: Read, W: Write> Index<isize> for State<'a, R, W> {
 
 impl<'a, R: Read, W: Write> IndexMut<isize> for State<'a, R, W> {
     fn index_mut(&mut self, index: isize) -> &mut u8 {
-        let idx = self.rel_index(index);
-        if idx >= self.memory.len() {
-            self.memory.resize(idx * 2 + 1, 0);
-        }
-        &mut self.memory[idx]
+        let idx = self.rel_index(index).unwrap();
+        self.cell_mut(idx)
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::State;
+    use super::{Eof, Error, State};
+
+    use crate::bytecode::Bytecode;
+    use crate::output::OutputBuffer;
 
     use crate::structs::Op::*;
     use crate::structs::OpStream;
-    use std::io::{empty, sink};
+    use std::io::{self, empty, sink, ErrorKind, Read};
+    use std::sync::atomic::{AtomicBool, Ordering};
 
     #[test]
     fn test_state_index() {
+        let mut output = sink();
         let mut state = State {
             index: 0,
             memory: vec![23, 0, 0, 0, 0, 42],
             input: &mut empty(),
-            output: &mut sink(),
+            output: OutputBuffer::new(&mut output),
+            steps_left: None,
+            tape_size: usize::MAX,
+            eof: Eof::Stop,
+            cancel: None,
         };
         assert_eq!(23, state[0]);
         state.index = 5;
@@ -150,7 +467,7 @@ mod tests {
         let mut output = sink();
         let mut state = State::new(&mut input, &mut output);
         let result = state.run(&[Add(1), Add(1)]);
-        assert!(result);
+        assert_eq!(Ok(true), result);
         assert_eq!(2, state[0]);
     }
 
@@ -160,7 +477,7 @@ mod tests {
         let mut output = sink();
         let mut state = State::new(&mut input, &mut output);
         let result = state.run(&[Add(1), In]);
-        assert!(!result);
+        assert_eq!(Ok(false), result);
         assert_eq!(1, state[0]);
     }
 
@@ -173,7 +490,135 @@ mod tests {
         let result = state.run(&[Loop(OpStream {
             ops: vec![Add(1), In],
         })]);
-        assert!(!result);
+        assert_eq!(Ok(false), result);
+        assert_eq!(2, state[0]);
+    }
+
+    #[test]
+    fn test_state_run_max_steps() {
+        let mut input = empty();
+        let mut output = sink();
+        let mut state = State::new(&mut input, &mut output);
+        state.set_max_steps(3);
+        let result = state.run(&[Add(1), Add(1), Add(1), Add(1)]);
+        assert_eq!(Err(Error::OutOfSteps), result);
+        assert_eq!(3, state[0]);
+    }
+
+    #[test]
+    fn test_state_run_max_steps_empty_loop() {
+        let mut input = empty();
+        let mut output = sink();
+        let mut state = State::new(&mut input, &mut output);
+        state.set_max_steps(100);
+        let result = state.run(&[Add(1), Loop(OpStream { ops: vec![] })]);
+        assert_eq!(Err(Error::OutOfSteps), result);
+    }
+
+    #[test]
+    fn test_state_run_max_steps_stalled_transfer() {
+        let mut input = empty();
+        let mut output = sink();
+        let mut state = State::new(&mut input, &mut output);
+        state.set_max_steps(100);
+        let result = state.run(&[Add(1), Transfer(2, vec![])]);
+        assert_eq!(Err(Error::OutOfSteps), result);
+    }
+
+    #[test]
+    fn test_state_run_non_termination() {
+        let mut input = empty();
+        let mut output = sink();
+        let mut state = State::new(&mut input, &mut output);
+        let empty_loop = || Loop(OpStream { ops: vec![] });
+        assert_eq!(Ok(true), state.run(&[empty_loop()]));
+        assert_eq!(
+            Err(Error::NonTermination),
+            state.run(&[Add(1), empty_loop()])
+        );
+        assert_eq!(
+            Err(Error::NonTermination),
+            state.run(&[Transfer(2, vec![(1, 1)])])
+        );
+        assert_eq!(0, state.memory().get(1).copied().unwrap_or(0));
+    }
+
+    #[test]
+    fn test_state_run_left_of_tape() {
+        let mut input = empty();
+        let mut output = sink();
+        let mut state = State::new(&mut input, &mut output);
+        let result = state.run(&[Add(1), Mov(-1), Add(1)]);
+        assert_eq!(Err(Error::PointerOutOfRange), result);
+        assert_eq!(0, state.index);
+        assert_eq!(1, state[0]);
+    }
+
+    #[test]
+    fn test_state_run_cancel() {
+        let cancel = AtomicBool::new(false);
+        let mut input = empty();
+        let mut output = sink();
+        let mut state = State::new(&mut input, &mut output);
+        state.set_cancel(&cancel);
+        assert_eq!(Ok(true), state.run(&[Add(1)]));
+        cancel.store(true, Ordering::Relaxed);
+        let program = [Loop(OpStream {
+            ops: vec![Mov(1), Add(1)],
+        })];
+        assert_eq!(Err(Error::Cancelled), state.run(&program));
+    }
+
+    /// Input that fails every read.
+    struct FailingInput;
+
+    impl Read for FailingInput {
+        fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+            Err(ErrorKind::InvalidData.into())
+        }
+    }
+
+    #[test]
+    fn test_state_run_input_error() {
+        let mut input = FailingInput;
+        let mut output = sink();
+        let mut state = State::new(&mut input, &mut output);
+        let result = state.run(&[In]);
+        assert_eq!(Err(Error::Input(ErrorKind::InvalidData)), result);
+        let result = state.run_bytecode(&Bytecode::compile(&[In]));
+        assert_eq!(Err(Error::Input(ErrorKind::InvalidData)), result);
+    }
+
+    #[test]
+    fn test_state_run_output_error() {
+        let mut input = empty();
+        let mut output = &mut [0u8; 0][..];
+        let mut state = State::new(&mut input, &mut output);
+        let program = [Add(1), Loop(OpStream { ops: vec![Out] })];
+        let result = state.run(&program);
+        assert_eq!(Err(Error::Output(ErrorKind::WriteZero)), result);
+    }
+
+    #[test]
+    fn test_state_run_tape_size() {
+        let mut input = empty();
+        let mut output = sink();
+        let mut state = State::new(&mut input, &mut output);
+        state.set_tape_size(3);
+        assert_eq!(Ok(true), state.run(&[Mov(2), Add(1)]));
+        assert_eq!(Err(Error::PointerOutOfRange), state.run(&[Mov(1)]));
+        assert_eq!(Err(Error::PointerOutOfRange), state.run(&[Mov(isize::MAX)]));
+        assert_eq!(3, state.memory.len());
+    }
+
+    #[test]
+    fn test_state_run_transfer_off_tape() {
+        let mut input = empty();
+        let mut output = sink();
+        let mut state = State::new(&mut input, &mut output);
+        state[0] = 2;
+        let result = state.run(&[Transfer(0xff, vec![(-1, 1)])]);
+        assert_eq!(Err(Error::PointerOutOfRange), result);
         assert_eq!(2, state[0]);
     }
 
@@ -182,11 +627,11 @@ mod tests {
         let mut input = empty();
         let mut output = sink();
         let mut state = State::new(&mut input, &mut output);
-        state.step(&Add(23));
+        state.step(&Add(23)).unwrap();
         assert_eq!(23, state[0]);
-        state.step(&Add(42));
+        state.step(&Add(42)).unwrap();
         assert_eq!(65, state[0]);
-        state.step(&Add(190));
+        state.step(&Add(190)).unwrap();
         assert_eq!(255, state[0]);
     }
 
@@ -195,11 +640,11 @@ mod tests {
         let mut input = empty();
         let mut output = sink();
         let mut state = State::new(&mut input, &mut output);
-        state.step(&Mov(1));
+        state.step(&Mov(1)).unwrap();
         assert_eq!(1, state.index);
-        state.step(&Mov(42));
+        state.step(&Mov(42)).unwrap();
         assert_eq!(43, state.index);
-        state.step(&Mov(-1));
+        state.step(&Mov(-1)).unwrap();
         assert_eq!(42, state.index);
     }
 
@@ -209,7 +654,7 @@ mod tests {
         let mut output = sink();
         let mut state = State::new(&mut input, &mut output);
         state[0] = 23;
-        state.step(&Loop(OpStream { ops: vec![Add(1)] }));
+        state.step(&Loop(OpStream { ops: vec![Add(1)] })).unwrap();
         assert_eq!(0, state[0]);
     }
 
@@ -220,7 +665,7 @@ mod tests {
         let mut state = State::new(&mut input, &mut output);
         state[0] = 15;
         state[1] = 7;
-        state.step(&Transfer(5, vec![(1, 2)]));
+        state.step(&Transfer(5, vec![(1, 2)])).unwrap();
         assert_eq!(0, state[0]);
         assert_eq!(1, state[1]);
     }
@@ -231,7 +676,7 @@ mod tests {
         let mut output = sink();
         let mut state = State::new(&mut input, &mut output);
         state[0] = 0;
-        state.step(&Transfer(5, vec![(1, 2)]));
+        state.step(&Transfer(5, vec![(1, 2)])).unwrap();
         assert_eq!(0, state[0]);
     }
 
@@ -241,7 +686,7 @@ mod tests {
         let mut output = sink();
         let mut state = State::new(&mut input, &mut output);
         let result = state.step(&In);
-        assert!(result);
+        assert_eq!(Ok(true), result);
         assert_eq!(23, state[0]);
     }
 
@@ -251,7 +696,20 @@ mod tests {
         let mut output = sink();
         let mut state = State::new(&mut input, &mut output);
         let result = state.step(&In);
-        assert!(!result);
+        assert_eq!(Ok(false), result);
+    }
+
+    #[test]
+    fn test_state_step_input_eof_modes() {
+        for (eof, expected) in [(Eof::Unchanged, 23), (Eof::Zero, 0), (Eof::MinusOne, 255)] {
+            let mut input = empty();
+            let mut output = sink();
+            let mut state = State::new(&mut input, &mut output);
+            state.set_eof(eof);
+            state[0] = 23;
+            assert_eq!(Ok(true), state.step(&In));
+            assert_eq!(expected, state[0]);
+        }
     }
 
     #[test]
@@ -261,7 +719,8 @@ mod tests {
         {
             let mut state = State::new(&mut input, &mut output);
             state[0] = 42;
-            state.step(&Out);
+            state.step(&Out).unwrap();
+            state.output.flush().unwrap();
         }
         assert_eq!(vec![42u8], output);
     }
@@ -0,0 +1,373 @@
+use std::fmt::Write;
+
+use crate::optimizer::transfer_factor;
+use crate::runner::{Config, Eof};
+use crate::structs::Op::*;
+use crate::structs::{contains, Op};
+
+const TAPE_SIZE: usize = 30000;
+
+/// Emits Rust source with the same behaviour as the operations: a program
+/// with `fn main()`, or with `library` set, a `pub fn run(input: impl Read,
+/// output: impl Write) -> io::Result<()>` to be included into other code. The
+/// tape has the configured size, 30000 cells by default, and leaving it is an
+/// error.
+pub fn emit_rust(ops: &[Op], config: &Config, library: bool) -> String {
+    let checks = contains(ops, &|op| match op {
+        Mov(_) => true,
+        Transfer(_, map) => !map.is_empty(),
+        _ => false,
+    });
+    let moves = contains(ops, &|op| matches!(op, Mov(_)));
+    let reads = contains(ops, &|op| matches!(op, In));
+    let writes = contains(ops, &|op| matches!(op, Out));
+    let changes = contains(ops, &|op| matches!(op, Add(_) | In | Transfer(_, _)));
+    let uses_tape = contains(ops, &|op| !matches!(op, Mov(_)));
+
+    let mut emitter = Emitter {
+        out: String::new(),
+        depth: 1,
+        eof: config.eof,
+        assigns: false,
+    };
+    emitter.emit(ops);
+
+    let mut out = String::new();
+    out.push_str("// Generated by brain_rust.\n\nuse std::io::{self, Read, Write};\n");
+    if !library {
+        out.push_str("use std::process;\n");
+    }
+    writeln!(
+        out,
+        "\nconst TAPE_SIZE: usize = {};",
+        config.tape_size.unwrap_or(TAPE_SIZE)
+    )
+    .unwrap();
+
+    if !library {
+        out.push_str(concat!(
+            "\n",
+            "fn main() {\n",
+            "    if let Err(e) = run(io::stdin().lock(), io::stdout().lock()) {\n",
+            "        eprintln!(\"{}\", e);\n",
+            "        process::exit(1);\n",
+            "    }\n",
+            "}\n",
+        ));
+    }
+
+    out.push('\n');
+    if library {
+        out.push_str("/// Runs the program until its end or the end of the input.\npub ");
+    }
+    writeln!(
+        out,
+        "fn run({}input: impl Read, {}output: impl Write) -> io::Result<()> {{",
+        if reads { "mut " } else { "_" },
+        if writes { "mut " } else { "_" }
+    )
+    .unwrap();
+    if uses_tape {
+        writeln!(
+            out,
+            "    let {}tape = vec![0u8; TAPE_SIZE];",
+            if changes { "mut " } else { "" }
+        )
+        .unwrap();
+    }
+    if uses_tape || moves {
+        let mutable = if emitter.assigns { "mut " } else { "" };
+        writeln!(out, "    let {}p = 0;", mutable).unwrap();
+    }
+    out.push_str(&emitter.out);
+    out.push_str("    Ok(())\n}\n");
+
+    if checks {
+        out.push_str(concat!(
+            "\n",
+            "/// The index of the cell at `offset` from `p`.\n",
+            "fn at(p: usize, offset: isize) -> io::Result<usize> {\n",
+            "    p.checked_add_signed(offset)\n",
+            "        .filter(|&i| i < TAPE_SIZE)\n",
+            "        .ok_or_else(|| io::Error::other(\"Pointer moved off the tape\"))\n",
+            "}\n",
+        ));
+    }
+
+    if reads {
+        let (result, eof) = match config.eof {
+            Eof::Stop => ("bool", "return Ok(false);"),
+            Eof::Unchanged => ("()", "return Ok(());"),
+            Eof::Zero => ("()", "c[0] = 0;"),
+            Eof::MinusOne => ("()", "c[0] = 255;"),
+        };
+        write!(
+            out,
+            concat!(
+                "\n",
+                "fn read(input: &mut impl Read, cell: &mut u8) -> io::Result<{}> {{\n",
+                "    let mut c = [0u8];\n",
+                "    if input.read(&mut c)? == 0 {{\n",
+                "        {}\n",
+                "    }}\n",
+                "    *cell = c[0];\n",
+                "    Ok({})\n",
+                "}}\n",
+            ),
+            result,
+            eof,
+            if config.eof == Eof::Stop {
+                "true"
+            } else {
+                "()"
+            }
+        )
+        .unwrap();
+    }
+
+    out
+}
+
+fn cell(offset: isize) -> String {
+    match offset {
+        0 => "tape[p]".to_string(),
+        k if k < 0 => format!("tape[p - {}]", k.unsigned_abs()),
+        k => format!("tape[p + {}]", k),
+    }
+}
+
+struct Emitter {
+    out: String,
+    depth: usize,
+    eof: Eof,
+    /// Whether a move assigns `p`.
+    assigns: bool,
+}
+
+impl Emitter {
+    fn line(&mut self, line: &str) {
+        for _ in 0..self.depth {
+            self.out.push_str("    ");
+        }
+        self.out.push_str(line);
+        self.out.push('\n');
+    }
+
+    fn add(&mut self, offset: isize, value: &str) {
+        let cell = cell(offset);
+        self.line(&format!("{} = {}.wrapping_add({});", cell, cell, value));
+    }
+
+    fn add_const(&mut self, offset: isize, value: u8) {
+        let cell = cell(offset);
+        if value < 0x80 {
+            self.line(&format!("{} = {}.wrapping_add({});", cell, cell, value));
+        } else {
+            let value = value.wrapping_neg();
+            self.line(&format!("{} = {}.wrapping_sub({});", cell, cell, value));
+        }
+    }
+
+    fn emit(&mut self, ops: &[Op]) {
+        for (index, op) in ops.iter().enumerate() {
+            match *op {
+                Add(i) => self.add_const(0, i),
+                // Nothing reads the pointer after the end of the program, so
+                // the last move there only needs to fail when it leaves the
+                // tape.
+                Mov(n) if self.depth == 1 && index + 1 == ops.len() => {
+                    self.line(&format!("at(p, {})?;", n));
+                }
+                Mov(n) => {
+                    self.assigns = true;
+                    self.line(&format!("p = at(p, {})?;", n));
+                }
+                In if self.eof == Eof::Stop => {
+                    self.line("if !read(&mut input, &mut tape[p])? {");
+                    self.line("    return Ok(());");
+                    self.line("}");
+                }
+                In => self.line("read(&mut input, &mut tape[p])?;"),
+                Out => self.line("output.write_all(&[tape[p]])?;"),
+                Loop(ref stream) => {
+                    self.line("while tape[p] != 0 {");
+                    self.depth += 1;
+                    self.emit(stream.get());
+                    self.depth -= 1;
+                    self.line("}");
+                }
+                Transfer(d, ref map) => self.emit_transfer(d, map),
+            }
+        }
+    }
+
+    fn emit_transfer(&mut self, d: u8, map: &[(isize, u8)]) {
+        // Even steps may stall, so they stay loops.
+        let Some(factor) = transfer_factor(d) else {
+            self.line("while tape[p] != 0 {");
+            self.depth += 1;
+            for &(k, _) in map {
+                self.line(&format!("at(p, {})?;", k));
+            }
+            self.add_const(0, d);
+            for &(k, v) in map {
+                self.add_const(k, v);
+            }
+            self.depth -= 1;
+            self.line("}");
+            return;
+        };
+        if map.is_empty() {
+            self.line("tape[p] = 0;");
+            return;
+        }
+
+        self.line("if tape[p] != 0 {");
+        self.depth += 1;
+        for &(k, _) in map {
+            self.line(&format!("at(p, {})?;", k));
+        }
+        if factor == 1 {
+            self.line("let n = tape[p];");
+        } else {
+            self.line(&format!("let n = tape[p].wrapping_mul({});", factor));
+        }
+        self.line("tape[p] = 0;");
+        for &(k, v) in map {
+            if v == 1 {
+                self.add(k, "n");
+            } else {
+                self.add(k, &format!("n.wrapping_mul({})", v));
+            }
+        }
+        self.depth -= 1;
+        self.line("}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::emit_rust;
+
+    use crate::runner::{Config, Eof};
+    use crate::structs::Op::*;
+    use crate::structs::OpStream;
+
+    #[test]
+    fn test_emit_rust() {
+        let ops = [
+            Add(3),
+            Loop(OpStream {
+                ops: vec![Mov(1), Add(0xfe), Out, Mov(-1), Add(0xff)],
+            }),
+        ];
+        let config = Config {
+            tape_size: Some(100),
+            ..Config::default()
+        };
+        assert_eq!(
+            emit_rust(&ops, &config, false),
+            r#"// Generated by brain_rust.
+
+use std::io::{self, Read, Write};
+use std::process;
+
+const TAPE_SIZE: usize = 100;
+
+fn main() {
+    if let Err(e) = run(io::stdin().lock(), io::stdout().lock()) {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+}
+
+fn run(_input: impl Read, mut output: impl Write) -> io::Result<()> {
+    let mut tape = vec![0u8; TAPE_SIZE];
+    let mut p = 0;
+    tape[p] = tape[p].wrapping_add(3);
+    while tape[p] != 0 {
+        p = at(p, 1)?;
+        tape[p] = tape[p].wrapping_sub(2);
+        output.write_all(&[tape[p]])?;
+        p = at(p, -1)?;
+        tape[p] = tape[p].wrapping_sub(1);
+    }
+    Ok(())
+}
+
+/// The index of the cell at `offset` from `p`.
+fn at(p: usize, offset: isize) -> io::Result<usize> {
+    p.checked_add_signed(offset)
+        .filter(|&i| i < TAPE_SIZE)
+        .ok_or_else(|| io::Error::other("Pointer moved off the tape"))
+}
+"#
+        );
+    }
+
+    #[test]
+    fn test_emit_rust_only_moves() {
+        let code = emit_rust(&[Mov(1), Mov(-2)], &Config::default(), true);
+        assert!(code.contains("    let mut p = 0;\n    p = at(p, 1)?;\n    at(p, -2)?;\n"));
+        assert!(!code.contains("let tape"));
+
+        let code = emit_rust(&[Add(1), Mov(1)], &Config::default(), true);
+        assert!(code
+            .contains("    let p = 0;\n    tape[p] = tape[p].wrapping_add(1);\n    at(p, 1)?;\n"));
+    }
+
+    #[test]
+    fn test_emit_rust_library() {
+        let ops = [
+            In,
+            Transfer(0xff, vec![(-1, 1), (2, 3)]),
+            Transfer(1, vec![]),
+        ];
+        let config = Config {
+            eof: Eof::MinusOne,
+            ..Config::default()
+        };
+        assert_eq!(
+            emit_rust(&ops, &config, true),
+            r#"// Generated by brain_rust.
+
+use std::io::{self, Read, Write};
+
+const TAPE_SIZE: usize = 30000;
+
+/// Runs the program until its end or the end of the input.
+pub fn run(mut input: impl Read, _output: impl Write) -> io::Result<()> {
+    let mut tape = vec![0u8; TAPE_SIZE];
+    let p = 0;
+    read(&mut input, &mut tape[p])?;
+    if tape[p] != 0 {
+        at(p, -1)?;
+        at(p, 2)?;
+        let n = tape[p];
+        tape[p] = 0;
+        tape[p - 1] = tape[p - 1].wrapping_add(n);
+        tape[p + 2] = tape[p + 2].wrapping_add(n.wrapping_mul(3));
+    }
+    tape[p] = 0;
+    Ok(())
+}
+
+/// The index of the cell at `offset` from `p`.
+fn at(p: usize, offset: isize) -> io::Result<usize> {
+    p.checked_add_signed(offset)
+        .filter(|&i| i < TAPE_SIZE)
+        .ok_or_else(|| io::Error::other("Pointer moved off the tape"))
+}
+
+fn read(input: &mut impl Read, cell: &mut u8) -> io::Result<()> {
+    let mut c = [0u8];
+    if input.read(&mut c)? == 0 {
+        c[0] = 255;
+    }
+    *cell = c[0];
+    Ok(())
+}
+"#
+        );
+    }
+}
@@ -0,0 +1,52 @@
+use proptest::prelude::*;
+
+use crate::structs::Op::*;
+use crate::structs::{Op, OpStream};
+
+/// Random programs with balanced loops, shaped like parsed ones: mostly
+/// single increments, decrements and moves. `In` and `Out` only appear if `io`
+/// is set.
+pub fn opstream(io: bool) -> impl Strategy<Value = OpStream> {
+    let add = prop_oneof![
+        3 => prop_oneof![Just(Add(0x01)), Just(Add(0xff))],
+        1 => any::<u8>().prop_map(Add),
+    ];
+    let mov = (-2isize..=2).prop_map(Mov);
+    let leaf = if io {
+        prop_oneof![4 => add, 4 => mov, 1 => Just(In), 1 => Just(Out)].boxed()
+    } else {
+        prop_oneof![add, mov].boxed()
+    };
+    let op = leaf.prop_recursive(3, 48, 8, |inner| {
+        prop::collection::vec(inner, 0..8).prop_map(|ops| Loop(OpStream { ops }))
+    });
+    prop::collection::vec(op, 0..16).prop_map(|ops| OpStream { ops })
+}
+
+/// The sum of all moves in a program, which bounds how far any single step of
+/// it or of its optimized form can move the pointer.
+pub fn total_movement(ops: &[Op]) -> usize {
+    ops.iter()
+        .map(|op| match op {
+            Mov(n) => n.unsigned_abs(),
+            Loop(stream) => total_movement(stream.get()),
+            Transfer(_, map) => map.iter().map(|(k, _)| k.unsigned_abs()).sum(),
+            _ => 0,
+        })
+        .sum()
+}
+
+/// Random source text with balanced loops, in which commands are mixed with
+/// words, spaces and line breaks.
+pub fn source() -> impl Strategy<Value = String> {
+    let leaf = prop_oneof![
+        4 => "[-+<>.,]{1,4}",
+        1 => "[a-z]{1,6}",
+        1 => Just(" ".to_string()),
+        1 => Just("\n".to_string()),
+    ];
+    let piece = leaf.prop_recursive(3, 48, 8, |inner| {
+        prop::collection::vec(inner, 0..8).prop_map(|pieces| format!("[{}]", pieces.concat()))
+    });
+    prop::collection::vec(piece, 0..16).prop_map(|pieces| pieces.concat())
+}
@@ -1,21 +1,42 @@
-#[derive(Debug, PartialEq, Eq)]
+/// A single operation of a program.
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum Op {
+    /// Adds to the current cell, wrapping around (`+` and `-`).
     Add(u8),
+    /// Moves the pointer by the given number of cells (`>` and `<`).
     Mov(isize),
+    /// Reads a byte into the current cell (`,`).
     In,
+    /// Writes the current cell (`.`).
     Out,
+    /// Repeats the contained operations while the current cell is not zero
+    /// (`[` and `]`).
     Loop(OpStream),
 
     // extra optimized ops
+    /// A loop that adds the first value to the current cell and each value of
+    /// the list to the cell at its offset on every iteration, until the
+    /// current cell is zero. Only created by the optimizer.
     Transfer(u8, Vec<(isize, u8)>),
 }
 
-#[derive(Debug, PartialEq, Eq)]
+/// A sequence of operations, such as a whole program or the body of a loop.
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct OpStream {
+    /// The operations in order.
     pub ops: Vec<Op>,
 }
 
+/// Whether any of the operations or of those in their loops matches.
+pub(crate) fn contains(ops: &[Op], predicate: &dyn Fn(&Op) -> bool) -> bool {
+    ops.iter().any(|op| match op {
+        Op::Loop(stream) => contains(stream.get(), predicate),
+        op => predicate(op),
+    })
+}
+
 impl OpStream {
+    /// The operations as a slice.
     pub fn get(&self) -> &[Op] {
         &self.ops[..]
     }
@@ -0,0 +1,432 @@
+//! Differential testing of the backends against each other, and of minified
+//! programs against the originals.
+
+use std::fmt;
+
+use crate::bytecode::Bytecode;
+#[cfg(feature = "cranelift")]
+use crate::cranelift_runner::CraneliftState;
+#[cfg(feature = "llvm")]
+use crate::llvm_runner::LlvmState;
+use crate::runner::{Config, Error, State};
+use crate::structs::OpStream;
+
+/// The tape of the JITs, which the interpreters get as well for [`verify`].
+const JIT_TAPE_SIZE: usize = 30000;
+
+/// Everything a program run can be observed by.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Outcome {
+    /// Everything the program wrote.
+    pub output: Vec<u8>,
+    /// The tape, possibly with trailing zeros.
+    pub memory: Vec<u8>,
+    /// The final position of the pointer.
+    pub index: usize,
+    /// Whether the program ran to its end (`Ok(true)`), stopped at the end of
+    /// the input (`Ok(false)`) or failed.
+    pub exit: Result<bool, Error>,
+}
+
+/// The first difference found between a backend and the reference.
+#[derive(Debug)]
+pub struct Difference {
+    backend: &'static str,
+    reference: &'static str,
+    detail: String,
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} differs from {}: {}",
+            self.backend, self.reference, self.detail
+        )
+    }
+}
+
+/// Why [`verify`] failed.
+#[derive(Debug)]
+pub enum VerifyError {
+    /// A backend's result differs from the reference.
+    Difference(Difference),
+    /// The reference run failed. The interpreters still agreed, unless the
+    /// step budget ran out, but the JITs weren't compared, as they don't
+    /// check everything the interpreter does.
+    Reference(Error),
+}
+
+impl fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            VerifyError::Difference(difference) => difference.fmt(f),
+            VerifyError::Reference(e) => write!(f, "interpreter failed: {}", e),
+        }
+    }
+}
+
+impl From<Difference> for VerifyError {
+    fn from(difference: Difference) -> VerifyError {
+        VerifyError::Difference(difference)
+    }
+}
+
+/// Runs the program with the interpreter.
+pub fn run_interpreter(opstream: &OpStream, input: &[u8], config: &Config) -> Outcome {
+    let mut input = input;
+    let mut output = vec![];
+    let (memory, index, exit) = {
+        let mut state = State::with_config(&mut input, &mut output, config);
+        let exit = state.run(opstream.get());
+        (state.memory().to_vec(), state.index(), exit)
+    };
+    Outcome {
+        output,
+        memory,
+        index,
+        exit,
+    }
+}
+
+/// Runs the program with the bytecode interpreter.
+pub fn run_bytecode(opstream: &OpStream, input: &[u8], config: &Config) -> Outcome {
+    let mut input = input;
+    let mut output = vec![];
+    let (memory, index, exit) = {
+        let mut state = State::with_config(&mut input, &mut output, config);
+        let exit = state.run_bytecode(&Bytecode::compile(opstream.get()));
+        (state.memory().to_vec(), state.index(), exit)
+    };
+    Outcome {
+        output,
+        memory,
+        index,
+        exit,
+    }
+}
+
+#[cfg(feature = "llvm")]
+/// Runs the program with the LLVM JIT.
+pub fn run_llvm(opstream: &OpStream, input: &[u8]) -> Outcome {
+    let mut input = input;
+    let mut output = vec![];
+    let (memory, index, exit) = {
+        let mut state = LlvmState::new(&mut input, &mut output, true);
+        let exit = state.run(opstream.get());
+        (state.memory().to_vec(), state.index(), exit)
+    };
+    Outcome {
+        output,
+        memory,
+        index,
+        exit,
+    }
+}
+
+/// Runs the program with the Cranelift JIT.
+#[cfg(feature = "cranelift")]
+pub fn run_cranelift(opstream: &OpStream, input: &[u8]) -> Outcome {
+    let mut input = input;
+    let mut output = vec![];
+    let (memory, index, exit) = {
+        let mut state = CraneliftState::new(&mut input, &mut output, true);
+        let exit = state.run(opstream.get());
+        (state.memory().to_vec(), state.index(), exit)
+    };
+    Outcome {
+        output,
+        memory,
+        index,
+        exit,
+    }
+}
+
+/// Runs the unoptimized program through the interpreter and compares the
+/// result with the one of every other backend, reporting the first difference.
+/// All interpreters run on the tape of the JITs, with at most `max_steps`
+/// steps, and the JITs only run programs that the reference runs to the end.
+pub fn verify(
+    opstream: &OpStream,
+    input: &[u8],
+    max_steps: Option<u64>,
+) -> Result<(), VerifyError> {
+    let config = Config {
+        max_steps,
+        tape_size: Some(JIT_TAPE_SIZE),
+        ..Config::default()
+    };
+    let reference = run_interpreter(opstream, input, &config);
+    // the optimized program takes fewer steps, so it may still end
+    if reference.exit == Err(Error::OutOfSteps) {
+        return Err(VerifyError::Reference(Error::OutOfSteps));
+    }
+
+    let mut optimized = opstream.clone();
+    optimized.optimize();
+    compare(
+        "interpreter",
+        &reference,
+        "optimized interpreter",
+        &run_interpreter(&optimized, input, &config),
+    )?;
+    compare(
+        "interpreter",
+        &reference,
+        "bytecode interpreter",
+        &run_bytecode(&optimized, input, &config),
+    )?;
+    if let Err(e) = reference.exit {
+        return Err(VerifyError::Reference(e));
+    }
+
+    #[cfg(feature = "llvm")]
+    compare(
+        "interpreter",
+        &reference,
+        "LLVM JIT",
+        &run_llvm(opstream, input),
+    )?;
+
+    #[cfg(feature = "cranelift")]
+    compare(
+        "interpreter",
+        &reference,
+        "Cranelift JIT",
+        &run_cranelift(&optimized, input),
+    )?;
+
+    Ok(())
+}
+
+/// Runs the original and the [`minify`](crate::minify)ed program through the
+/// interpreter and compares what they write and how they end, reporting the
+/// first difference.
+pub fn verify_minified(
+    original: &OpStream,
+    minified: &OpStream,
+    input: &[u8],
+) -> Result<(), Difference> {
+    let expected = run_interpreter(original, input, &Config::default());
+    let actual = run_interpreter(minified, input, &Config::default());
+    let difference = |detail| Difference {
+        backend: "minified program",
+        reference: "original program",
+        detail,
+    };
+    if let Some(detail) = compare_output(&expected, &actual) {
+        return Err(difference(detail));
+    }
+    if let Some(detail) = compare_exit(&expected, &actual) {
+        return Err(difference(detail));
+    }
+    Ok(())
+}
+
+fn compare(
+    reference: &'static str,
+    expected: &Outcome,
+    backend: &'static str,
+    actual: &Outcome,
+) -> Result<(), Difference> {
+    let difference = |detail| Difference {
+        backend,
+        reference,
+        detail,
+    };
+
+    if let Some(detail) = compare_output(expected, actual) {
+        return Err(difference(detail));
+    }
+    // Where a failing program stops depends on how its moves were merged.
+    if expected.exit.is_err() {
+        return compare_exit(expected, actual).map_or(Ok(()), |detail| Err(difference(detail)));
+    }
+
+    // The tapes grow differently, so cells beyond the end count as zero.
+    let len = expected.memory.len().max(actual.memory.len());
+    let cell = |memory: &[u8], i| memory.get(i).copied().unwrap_or(0);
+    if let Some(i) = (0..len).find(|&i| cell(&expected.memory, i) != cell(&actual.memory, i)) {
+        return Err(difference(format!(
+            "cell {} is {:#04x} instead of {:#04x}",
+            i,
+            cell(&actual.memory, i),
+            cell(&expected.memory, i)
+        )));
+    }
+
+    if expected.index != actual.index {
+        return Err(difference(format!(
+            "pointer ends at {} instead of {}",
+            actual.index, expected.index
+        )));
+    }
+
+    if let Some(detail) = compare_exit(expected, actual) {
+        return Err(difference(detail));
+    }
+
+    Ok(())
+}
+
+fn compare_output(expected: &Outcome, actual: &Outcome) -> Option<String> {
+    let i = first_mismatch(&expected.output, &actual.output)?;
+    Some(match (expected.output.get(i), actual.output.get(i)) {
+        (Some(e), Some(a)) => format!("output byte {} is {:#04x} instead of {:#04x}", i, a, e),
+        (Some(_), None) => format!("output ends after {} bytes", i),
+        _ => format!("output has extra bytes after {} bytes", i),
+    })
+}
+
+fn compare_exit(expected: &Outcome, actual: &Outcome) -> Option<String> {
+    (expected.exit != actual.exit).then(|| {
+        format!(
+            "program ended with {} instead of {}",
+            describe_exit(&actual.exit),
+            describe_exit(&expected.exit)
+        )
+    })
+}
+
+fn describe_exit(exit: &Result<bool, Error>) -> String {
+    match exit {
+        Ok(true) => "completion".into(),
+        Ok(false) => "end of input".into(),
+        Err(e) => format!("error \"{}\"", e),
+    }
+}
+
+fn first_mismatch(expected: &[u8], actual: &[u8]) -> Option<usize> {
+    expected
+        .iter()
+        .zip(actual)
+        .position(|(e, a)| e != a)
+        .or((expected.len() != actual.len()).then(|| expected.len().min(actual.len())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{compare, run_interpreter, verify, verify_minified, Outcome, VerifyError};
+
+    use crate::minifier::minify;
+    use crate::parser::parse;
+    use crate::runner::{Config, Error};
+    use crate::structs::OpStream;
+
+    fn outcome(output: &[u8], memory: &[u8], index: usize, completed: bool) -> Outcome {
+        Outcome {
+            output: output.to_vec(),
+            memory: memory.to_vec(),
+            index,
+            exit: Ok(completed),
+        }
+    }
+
+    #[test]
+    fn test_run_interpreter() {
+        let opstream = OpStream {
+            ops: parse(b",[->++<]>.").unwrap(),
+        };
+        assert_eq!(
+            run_interpreter(&opstream, b"\x03", &Config::default()),
+            outcome(b"\x06", &[0, 6, 0], 1, true)
+        );
+    }
+
+    #[test]
+    fn test_verify() {
+        let opstream = OpStream {
+            ops: parse(b"++[>+++[>++<-]<-]>>.,[-.,]").unwrap(),
+        };
+        assert!(verify(&opstream, b"abc", None).is_ok());
+    }
+
+    #[test]
+    fn test_verify_reference_error() {
+        let reference_error = |program: &[u8], max_steps| {
+            let opstream = OpStream {
+                ops: parse(program).unwrap(),
+            };
+            match verify(&opstream, b"", max_steps) {
+                Err(VerifyError::Reference(e)) => Some(e),
+                _ => None,
+            }
+        };
+        // the JITs would write outside their tape or never end
+        assert_eq!(reference_error(b"<+", None), Some(Error::PointerOutOfRange));
+        assert_eq!(
+            reference_error(&[b'>'; 30000], None),
+            Some(Error::PointerOutOfRange)
+        );
+        assert_eq!(reference_error(b"+[]", None), Some(Error::NonTermination));
+        assert_eq!(
+            reference_error(b"+[>+<]", Some(1000)),
+            Some(Error::OutOfSteps)
+        );
+    }
+
+    #[test]
+    fn test_verify_minified() {
+        let original = OpStream {
+            ops: parse(b"++[>+++<-]>.").unwrap(),
+        };
+        let minified = OpStream {
+            ops: parse(b"++[>+++<-]>").unwrap(),
+        };
+        assert!(verify_minified(&original, &minify(original.get()), b"").is_ok());
+        assert_eq!(
+            verify_minified(&original, &minified, b"")
+                .unwrap_err()
+                .to_string(),
+            "minified program differs from original program: output ends after 0 bytes"
+        );
+    }
+
+    #[test]
+    fn test_compare_equal() {
+        let expected = outcome(b"ab", &[1, 2], 1, true);
+        let actual = outcome(b"ab", &[1, 2, 0, 0, 0], 1, true);
+        assert!(compare("a", &expected, "b", &actual).is_ok());
+    }
+
+    #[test]
+    fn test_compare_output() {
+        let expected = outcome(b"ab", &[], 0, true);
+        assert_eq!(
+            compare("a", &expected, "b", &outcome(b"ac", &[], 0, true))
+                .unwrap_err()
+                .to_string(),
+            "b differs from a: output byte 1 is 0x63 instead of 0x62"
+        );
+        assert_eq!(
+            compare("a", &expected, "b", &outcome(b"a", &[], 0, true))
+                .unwrap_err()
+                .to_string(),
+            "b differs from a: output ends after 1 bytes"
+        );
+    }
+
+    #[test]
+    fn test_compare_memory() {
+        let expected = outcome(b"", &[1, 2], 0, true);
+        let actual = outcome(b"", &[1, 2, 0, 3], 0, true);
+        assert_eq!(
+            compare("a", &expected, "b", &actual)
+                .unwrap_err()
+                .to_string(),
+            "b differs from a: cell 3 is 0x03 instead of 0x00"
+        );
+    }
+
+    #[test]
+    fn test_compare_exit() {
+        let expected = outcome(b"", &[], 0, true);
+        let actual = outcome(b"", &[], 0, false);
+        assert_eq!(
+            compare("a", &expected, "b", &actual)
+                .unwrap_err()
+                .to_string(),
+            "b differs from a: program ended with end of input instead of completion"
+        );
+    }
+}
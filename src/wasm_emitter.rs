@@ -0,0 +1,513 @@
+use std::fmt::Write;
+
+use crate::optimizer::transfer_factor;
+use crate::runner::{Config, Eof};
+use crate::structs::Op;
+
+const TAPE_SIZE: usize = 30000;
+const PAGE_SIZE: usize = 65536;
+
+// Indices of the imported functions and of the locals of `run`.
+const GETCHAR: u32 = 0;
+const PUTCHAR: u32 = 1;
+const P: u32 = 0;
+const N: u32 = 1;
+
+const FUNCTIONS: [&str; 3] = ["getchar", "putchar", "run"];
+const LOCALS: [&str; 2] = ["p", "n"];
+
+/// The instructions the emitted code is made of, all operating on `i32`.
+#[derive(Clone, Copy, Debug)]
+enum Ins {
+    Block,
+    Loop,
+    If,
+    End,
+    Br(u32),
+    BrIf(u32),
+    Return,
+    Call(u32),
+    Select,
+    LocalGet(u32),
+    LocalSet(u32),
+    LocalTee(u32),
+    Load8,
+    Store8,
+    Const(i32),
+    Eqz,
+    LtS,
+    LtU,
+    GeS,
+    GeU,
+    Add,
+    Mul,
+}
+
+use Ins::*;
+
+/// Emits a WebAssembly module with the same behaviour as the operations, in
+/// the text format. See [`emit_wasm`] for its interface.
+///
+/// # Panics
+///
+/// If the configured tape is larger than 4 GiB.
+pub fn emit_wat(ops: &[Op], config: &Config) -> String {
+    let (code, pages) = compile(ops, config);
+
+    let mut out = String::new();
+    out.push_str(concat!(
+        "(module\n",
+        "  (import \"env\" \"getchar\" (func $getchar (result i32)))\n",
+        "  (import \"env\" \"putchar\" (func $putchar (param i32)))\n",
+    ));
+    writeln!(out, "  (memory (export \"memory\") {})", pages).unwrap();
+    out.push_str("  (func $run (export \"run\") (result i32)\n");
+    for local in LOCALS {
+        writeln!(out, "    (local ${} i32)", local).unwrap();
+    }
+    let mut depth = 2;
+    for ins in code {
+        if let End = ins {
+            depth -= 1;
+        }
+        for _ in 0..depth {
+            out.push_str("  ");
+        }
+        match ins {
+            Block => out.push_str("block"),
+            Loop => out.push_str("loop"),
+            If => out.push_str("if"),
+            End => out.push_str("end"),
+            Br(l) => write!(out, "br {}", l).unwrap(),
+            BrIf(l) => write!(out, "br_if {}", l).unwrap(),
+            Return => out.push_str("return"),
+            Call(f) => write!(out, "call ${}", FUNCTIONS[f as usize]).unwrap(),
+            Select => out.push_str("select"),
+            LocalGet(l) => write!(out, "local.get ${}", LOCALS[l as usize]).unwrap(),
+            LocalSet(l) => write!(out, "local.set ${}", LOCALS[l as usize]).unwrap(),
+            LocalTee(l) => write!(out, "local.tee ${}", LOCALS[l as usize]).unwrap(),
+            Load8 => out.push_str("i32.load8_u"),
+            Store8 => out.push_str("i32.store8"),
+            Const(i) => write!(out, "i32.const {}", i).unwrap(),
+            Eqz => out.push_str("i32.eqz"),
+            LtS => out.push_str("i32.lt_s"),
+            LtU => out.push_str("i32.lt_u"),
+            GeS => out.push_str("i32.ge_s"),
+            GeU => out.push_str("i32.ge_u"),
+            Add => out.push_str("i32.add"),
+            Mul => out.push_str("i32.mul"),
+        }
+        out.push('\n');
+        if let Block | Loop | If = ins {
+            depth += 1;
+        }
+    }
+    out.push_str("    i32.const 0\n  )\n)\n");
+    out
+}
+
+/// Emits a binary WebAssembly module with the same behaviour as the
+/// operations.
+///
+/// The module imports `getchar: () -> i32`, which returns the next byte of
+/// the input or -1 at its end, and `putchar: (i32) -> ()` from `env`. It
+/// exports its memory as `memory`, holding the tape at address 0, and
+/// `run: () -> i32`, which runs the program and returns 0, or 1 if the
+/// pointer left the tape. The tape has the configured size, 30000 cells by
+/// default.
+///
+/// # Panics
+///
+/// If the configured tape is larger than 4 GiB.
+pub fn emit_wasm(ops: &[Op], config: &Config) -> Vec<u8> {
+    let (code, pages) = compile(ops, config);
+
+    let mut out = b"\0asm\x01\0\0\0".to_vec();
+    // types: () -> i32 and (i32) -> ()
+    section(&mut out, 1, &[2, 0x60, 0, 1, 0x7f, 0x60, 1, 0x7f, 0]);
+    let mut imports = vec![2];
+    for (name, ty) in [("getchar", 0), ("putchar", 1)] {
+        string(&mut imports, "env");
+        string(&mut imports, name);
+        imports.extend([0, ty]);
+    }
+    section(&mut out, 2, &imports);
+    section(&mut out, 3, &[1, 0]);
+    let mut memories = vec![1, 0];
+    unsigned(&mut memories, pages as u64);
+    section(&mut out, 5, &memories);
+    let mut exports = vec![2];
+    string(&mut exports, "memory");
+    exports.extend([2, 0]);
+    string(&mut exports, "run");
+    exports.extend([0, 2]);
+    section(&mut out, 7, &exports);
+
+    let mut body = vec![1, LOCALS.len() as u8, 0x7f];
+    for ins in code {
+        match ins {
+            Block => body.extend([0x02, 0x40]),
+            Loop => body.extend([0x03, 0x40]),
+            If => body.extend([0x04, 0x40]),
+            End => body.push(0x0b),
+            Br(l) => body.extend([0x0c, l as u8]),
+            BrIf(l) => body.extend([0x0d, l as u8]),
+            Return => body.push(0x0f),
+            Call(f) => body.extend([0x10, f as u8]),
+            Select => body.push(0x1b),
+            LocalGet(l) => body.extend([0x20, l as u8]),
+            LocalSet(l) => body.extend([0x21, l as u8]),
+            LocalTee(l) => body.extend([0x22, l as u8]),
+            Load8 => body.extend([0x2d, 0, 0]),
+            Store8 => body.extend([0x3a, 0, 0]),
+            Const(i) => {
+                body.push(0x41);
+                signed(&mut body, i.into());
+            }
+            Eqz => body.push(0x45),
+            LtS => body.push(0x48),
+            LtU => body.push(0x49),
+            GeS => body.push(0x4e),
+            GeU => body.push(0x4f),
+            Add => body.push(0x6a),
+            Mul => body.push(0x6c),
+        }
+    }
+    body.extend([0x41, 0, 0x0b]);
+    let mut bodies = vec![1];
+    unsigned(&mut bodies, body.len() as u64);
+    bodies.extend(body);
+    section(&mut out, 10, &bodies);
+
+    // names of the functions and locals, for debuggers
+    let mut names = vec![];
+    string(&mut names, "name");
+    let mut functions = vec![FUNCTIONS.len() as u8];
+    for (i, name) in FUNCTIONS.iter().enumerate() {
+        functions.push(i as u8);
+        string(&mut functions, name);
+    }
+    section(&mut names, 1, &functions);
+    let mut locals = vec![1, 2, LOCALS.len() as u8];
+    for (i, name) in LOCALS.iter().enumerate() {
+        locals.push(i as u8);
+        string(&mut locals, name);
+    }
+    section(&mut names, 2, &locals);
+    section(&mut out, 0, &names);
+    out
+}
+
+fn unsigned(out: &mut Vec<u8>, mut value: u64) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+fn signed(out: &mut Vec<u8>, mut value: i64) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if (value == 0 && byte & 0x40 == 0) || (value == -1 && byte & 0x40 != 0) {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+fn string(out: &mut Vec<u8>, string: &str) {
+    unsigned(out, string.len() as u64);
+    out.extend(string.as_bytes());
+}
+
+fn section(out: &mut Vec<u8>, id: u8, contents: &[u8]) {
+    out.push(id);
+    unsigned(out, contents.len() as u64);
+    out.extend(contents);
+}
+
+/// The body of `run` without its final result, and the number of memory
+/// pages.
+fn compile(ops: &[Op], config: &Config) -> (Vec<Ins>, usize) {
+    let tape_size = config.tape_size.unwrap_or(TAPE_SIZE);
+    assert!(
+        tape_size as u64 <= 1 << 32,
+        "The tape is too large for WebAssembly"
+    );
+    let mut emitter = Emitter {
+        code: vec![],
+        tape_size,
+        eof: config.eof,
+    };
+    emitter.emit(ops);
+    (emitter.code, tape_size.div_ceil(PAGE_SIZE))
+}
+
+struct Emitter {
+    code: Vec<Ins>,
+    tape_size: usize,
+    eof: Eof,
+}
+
+impl Emitter {
+    fn push(&mut self, code: &[Ins]) {
+        self.code.extend(code);
+    }
+
+    /// Returns 1 unless the cell at `offset` from the pointer is on the tape.
+    fn check(&mut self, offset: isize) {
+        let (limit, compare) = if offset < 0 {
+            (offset.unsigned_abs(), LtU)
+        } else {
+            (self.tape_size.saturating_sub(offset as usize), GeU)
+        };
+        match u32::try_from(limit) {
+            Ok(limit) => self.push(&[
+                LocalGet(P),
+                Const(limit as i32),
+                compare,
+                If,
+                Const(1),
+                Return,
+                End,
+            ]),
+            Err(_) => self.push(&[Const(1), Return]),
+        }
+    }
+
+    /// Pushes the address of the cell at `offset` from the pointer, which
+    /// wraps around like the memory addresses do.
+    fn address(&mut self, offset: isize) {
+        self.push(&[LocalGet(P)]);
+        if offset != 0 {
+            self.push(&[Const(offset as i32), Add]);
+        }
+    }
+
+    fn add(&mut self, offset: isize, value: &[Ins]) {
+        self.address(offset);
+        self.address(offset);
+        self.push(&[Load8]);
+        self.push(value);
+        self.push(&[Add, Store8]);
+    }
+
+    fn emit(&mut self, ops: &[Op]) {
+        for op in ops {
+            match *op {
+                Op::Add(i) => self.add(0, &[Const(i as i8 as i32)]),
+                Op::Mov(n) => {
+                    self.check(n);
+                    self.address(n);
+                    self.push(&[LocalSet(P)]);
+                }
+                Op::In => self.emit_in(),
+                Op::Out => self.push(&[LocalGet(P), Load8, Call(PUTCHAR)]),
+                Op::Loop(ref stream) => {
+                    self.push(&[Block, Loop, LocalGet(P), Load8, Eqz, BrIf(1)]);
+                    self.emit(stream.get());
+                    self.push(&[Br(0), End, End]);
+                }
+                Op::Transfer(d, ref map) => self.emit_transfer(d, map),
+            }
+        }
+    }
+
+    fn emit_in(&mut self) {
+        match self.eof {
+            Eof::Stop => self.push(&[
+                Call(GETCHAR),
+                LocalTee(N),
+                Const(0),
+                LtS,
+                If,
+                Const(0),
+                Return,
+                End,
+                LocalGet(P),
+                LocalGet(N),
+                Store8,
+            ]),
+            Eof::Unchanged => self.push(&[
+                Call(GETCHAR),
+                LocalTee(N),
+                Const(0),
+                GeS,
+                If,
+                LocalGet(P),
+                LocalGet(N),
+                Store8,
+                End,
+            ]),
+            Eof::Zero => self.push(&[
+                LocalGet(P),
+                Call(GETCHAR),
+                LocalTee(N),
+                Const(0),
+                LocalGet(N),
+                Const(0),
+                GeS,
+                Select,
+                Store8,
+            ]),
+            // -1 is stored as 255
+            Eof::MinusOne => self.push(&[LocalGet(P), Call(GETCHAR), Store8]),
+        }
+    }
+
+    fn emit_transfer(&mut self, d: u8, map: &[(isize, u8)]) {
+        // Even steps may stall, so they stay loops.
+        let Some(factor) = transfer_factor(d) else {
+            self.push(&[Block, Loop, LocalGet(P), Load8, Eqz, BrIf(1)]);
+            for &(k, _) in map {
+                self.check(k);
+            }
+            self.add(0, &[Const(d.into())]);
+            for &(k, v) in map {
+                self.add(k, &[Const(v.into())]);
+            }
+            self.push(&[Br(0), End, End]);
+            return;
+        };
+        if map.is_empty() {
+            self.push(&[LocalGet(P), Const(0), Store8]);
+            return;
+        }
+
+        self.push(&[LocalGet(P), Load8, If]);
+        for &(k, _) in map {
+            self.check(k);
+        }
+        self.push(&[LocalGet(P), Load8]);
+        if factor != 1 {
+            self.push(&[Const(factor.into()), Mul]);
+        }
+        self.push(&[LocalSet(N), LocalGet(P), Const(0), Store8]);
+        for &(k, v) in map {
+            if v == 1 {
+                self.add(k, &[LocalGet(N)]);
+            } else {
+                self.add(k, &[LocalGet(N), Const(v.into()), Mul]);
+            }
+        }
+        self.push(&[End]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{emit_wasm, emit_wat};
+
+    use crate::runner::{Config, Eof};
+    use crate::structs::Op::*;
+    use crate::structs::{Op, OpStream};
+
+    fn ops() -> Vec<Op> {
+        vec![
+            Add(3),
+            Loop(OpStream {
+                ops: vec![Mov(1), Add(0xfe), Out, Mov(-1), Add(0xff)],
+            }),
+            In,
+            Transfer(0xff, vec![(-1, 1), (2, 3)]),
+            Transfer(2, vec![(1, 1)]),
+            Transfer(1, vec![]),
+        ]
+    }
+
+    #[test]
+    fn test_emit_wat() {
+        let config = Config {
+            tape_size: Some(100),
+            ..Config::default()
+        };
+        assert_eq!(
+            emit_wat(&ops()[..2], &config),
+            r#"(module
+  (import "env" "getchar" (func $getchar (result i32)))
+  (import "env" "putchar" (func $putchar (param i32)))
+  (memory (export "memory") 1)
+  (func $run (export "run") (result i32)
+    (local $p i32)
+    (local $n i32)
+    local.get $p
+    local.get $p
+    i32.load8_u
+    i32.const 3
+    i32.add
+    i32.store8
+    block
+      loop
+        local.get $p
+        i32.load8_u
+        i32.eqz
+        br_if 1
+        local.get $p
+        i32.const 99
+        i32.ge_u
+        if
+          i32.const 1
+          return
+        end
+        local.get $p
+        i32.const 1
+        i32.add
+        local.set $p
+        local.get $p
+        local.get $p
+        i32.load8_u
+        i32.const -2
+        i32.add
+        i32.store8
+        local.get $p
+        i32.load8_u
+        call $putchar
+        local.get $p
+        i32.const 1
+        i32.lt_u
+        if
+          i32.const 1
+          return
+        end
+        local.get $p
+        i32.const -1
+        i32.add
+        local.set $p
+        local.get $p
+        local.get $p
+        i32.load8_u
+        i32.const -1
+        i32.add
+        i32.store8
+        br 0
+      end
+    end
+    i32.const 0
+  )
+)
+"#
+        );
+    }
+
+    #[test]
+    fn test_emit_wasm_matches_wat() {
+        for eof in [Eof::Stop, Eof::Unchanged, Eof::Zero, Eof::MinusOne] {
+            let config = Config {
+                tape_size: Some(1 << 20),
+                eof,
+                ..Config::default()
+            };
+            assert_eq!(
+                wat::parse_str(emit_wat(&ops(), &config)).unwrap(),
+                emit_wasm(&ops(), &config)
+            );
+        }
+    }
+}
@@ -0,0 +1,4 @@
+Echoes two bytes of input
+The program stops at the end of input so the final X is never printed
+
+,.,.,.,.++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++.
@@ -0,0 +1,4 @@
+Prints Hello World followed by a newline
+From the Wikipedia article on Brainfuck
+
+++++++++[>++++[>++>+++>+++>+<<<<-]>+>+>->>+[<]<-]>>.>---.+++++++..+++.>>.<-.<.+++.------.--------.>>+.>++.
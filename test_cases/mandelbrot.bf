@@ -0,0 +1,137 @@
+Draws the Mandelbrot set as 41 by 17 characters
+Uses sign and magnitude fixed point numbers with 4 fractional bits
+and at most 15 iterations per character
+
+>>----------------<<+++++++++++++++++[->>[-
+>>>>>>>>>>>>>>>>>>>+>>>>>>>+<<<<<<<<<<<<<<<<<<<<<<<<<<]>>>>>>>>>>>>>>>>>>>>>>>>
+>>[-
+<<<<<<<<<<<<<<<<<<<<<<<<<<+>>>>>>>>>>>>>>>>>>>>>>>>>>]<<<<<<<<<<<<<<<<<<<<<<<<<
+<[-
+>>>>>>>>>>>>>>>>>>>>>>+>>>>+<<<<<<<<<<<<<<<<<<<<<<<<<<]>>>>>>>>>>>>>>>>>>>>>>>>
+>>[-
+<<<<<<<<<<<<<<<<<<<<<<<<<<+>>>>>>>>>>>>>>>>>>>>>>>>>>]<+<<<<<<<<<<<<<<<<<<<<<<<
+<+<[>-]>[->>>>>>>>>>>>>>>>>>>>>>>>[-
+]<<<<<<<<<<<<<<<<<<<<<<<<>]<<>>>>>>>>>>>>>>>>>>>>>>>>>[<<<<<<+>>>-
+<<<<<<<<<<<<<<<+>>>>>>>>>>>>>+<[>-]>[->>>>>[-
+]<<<<<<<<<<<<<<<<<<<+>>>>>>>>>>>>>>>]<<>>>>+<[>-]>[->>[-]<<>]<<>>>]<<<<<<[-
+]>>>[+]<<<<<<<<<<<<<<<<<<<[-]--------------------------------
+<<<<+++++++++++++++++++++++++++++++++++++++++[->>>>[-
+>>>>>>>>>>>>>>>>+>>>>>>>+<<<<<<<<<<<<<<<<<<<<<<<]>>>>>>>>>>>>>>>>>>>>>>>[-
+<<<<<<<<<<<<<<<<<<<<<<<+>>>>>>>>>>>>>>>>>>>>>>>]<<<<<<<<<<<<<<<<<<<<<<<[-
+>>>>>>>>>>>>>>>>>>>+>>>>+<<<<<<<<<<<<<<<<<<<<<<<]>>>>>>>>>>>>>>>>>>>>>>>[-
+<<<<<<<<<<<<<<<<<<<<<<<+>>>>>>>>>>>>>>>>>>>>>>>]<+<<<<<<<<<<<<<<<<<<<<<+<[>-
+]>[->>>>>>>>>>>>>>>>>>>>>[-
+]<<<<<<<<<<<<<<<<<<<<<>]<<>>>>>>>>>>>>>>>>>>>>>>[<<<<<<+>>>-
+<<<<<<<<<<<<<+>>>>>>>>>>>+<[>-]>[->>>>>[-
+]<<<<<<<<<<<<<<<<<+>>>>>>>>>>>>>]<<>>>>+<[>-]>[->>[-]<<>]<<>>>]<<<<<<[-
+]>>>[+]<<<<<<<+++++++++++++++>>>+[[-]<<<<<<<[-
+>>>>>>>>>>>>+>+<<<<<<<<<<<<<]>>>>>>>>>>>>>[-
+<<<<<<<<<<<<<+>>>>>>>>>>>>>]++++++++++++++++<[->->+<[>-]>[-
+<++++++++++++++++<<<<<+>>>>>>>]<<<]<<<++++++++++++++++>>>>[-<<<<-
+>>>>]<<<<<<<<<<<[->>>>>>>>>>+>+<<<<<<<<<<<]>>>>>>>>>>>[-
+<<<<<<<<<<<+>>>>>>>>>>>]++++++++++++++++<[->->+<[>-]>[-
+<++++++++++++++++<<<+>>>>>]<<<]<++++++++++++++++>>[-<<->>]<<<<<[-
+>>>>>>>+>>>+<<<<<<<<<<]>>>>>>>>>>[-<<<<<<<<<<+>>>>>>>>>>]++<<<[->>>->+<[>-]>[-
+<++<<+>>>>]<<<<<]>>++>[-<->]<<[-<<<<+>>>>]>[-]<<<<<<<[-
+>>>>>+>>>+<<<<<<<<]>>>>>>>>[-<<<<<<<<+>>>>>>>>]++<<<[->>>->+<[>-]>[-
+<++<<+>>>>]<<<<<]>>++>[-<->]<<[-<<<<+>>>>]>[-]<<<<+<[>-]>[-<<<<<[-
+>>>>>>>>>+>+<<<<<<<<<<]>>>>>>>>>>[-<<<<<<<<<<+>>>>>>>>>>]<[[-]<<<<<<<<<[-
+>>>>>>>>>>+>+<<<<<<<<<<<]>>>>>>>>>>>[-<<<<<<<<<<<+>>>>>>>>>>>]<[[-
+]<<<++++++++++++++++>>>]<<<<<<<<<[->>>>>>+>>>+<<<<<<<<<]>>>>>>>>>[-
+<<<<<<<<<+>>>>>>>>>]<]<<<<<<<<<[->>>>>>>>>+>+<<<<<<<<<<]>>>>>>>>>>[-
+<<<<<<<<<<+>>>>>>>>>>]<[[-]<<<<<<<<[->>>>>>+>>>+<<<<<<<<<]>>>>>>>>>[-
+<<<<<<<<<+>>>>>>>>>]<]++++++++++++++++<<<<<<<<[-
+>>>>>>>>>>>>>>+>+<<<<<<<<<<<<<<<]>>>>>>>>>>>>>>>[-
+<<<<<<<<<<<<<<<+>>>>>>>>>>>>>>>]<<<<<<<<<<<<<<<[-
+>>>>>>>>>>>>>+>>+<<<<<<<<<<<<<<<]>>>>>>>>>>>>>>>[-
+<<<<<<<<<<<<<<<+>>>>>>>>>>>>>>>]<[-<[-<<+>>>>+<<]>>[-<<+>>]<<<<[-<<<->+<[>-]>[-
+<++++++++++++++++>>>>+<<<>]<<>>>]>>>]<<<<<<[-]>>>>>[-]<[-
+<<<<<<+>>>>>>]<<<<<<<<<<<[->>>>>>>+>+<<<<<<<<]>>>>>>>>[-<<<<<<<<+>>>>>>>>]<[[-
+]<<<<<<<[->>>>>>>>+>+<<<<<<<<<]>>>>>>>>>[-<<<<<<<<<+>>>>>>>>>]<[[-
+]<<++++++++++++++++>>]<<<<<<<[->>>>>+>>+<<<<<<<]>>>>>>>[-
+<<<<<<<+>>>>>>>]<]<<<<<<<[->>>>>>>+>+<<<<<<<<]>>>>>>>>[-<<<<<<<<+>>>>>>>>]<[[-
+]<<<<<<[->>>>>+>>+<<<<<<<]>>>>>>>[-<<<<<<<+>>>>>>>]<]++++++++++++++++<<<<<<[-
+>>>>>>>>>>>>+>+<<<<<<<<<<<<<]>>>>>>>>>>>>>[-
+<<<<<<<<<<<<<+>>>>>>>>>>>>>]<<<<<<<<<<<<<[-
+>>>>>>>>>>>+>>+<<<<<<<<<<<<<]>>>>>>>>>>>>>[-<<<<<<<<<<<<<+>>>>>>>>>>>>>]<[-<[-
+<<+>>>>+<<]>>[-<<+>>]<<<<[-<<<->+<[>-]>[-
+<++++++++++++++++>>>>+<<<>]<<>>>]>>>]<<<<<<[-]>>>>>[-]<[-<<<<<+>>>>>]<<<<<<[-
+>>>>>+>>+<<<<<<<]>>>>>>>[-<<<<<<<+>>>>>>>]<<<<<<[->>>>+>>+<<<<<<]>>>>>>[-
+<<<<<<+>>>>>>]+++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++
+<<[->>->+<[>-]>[-
+<+++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++<<<<<+>>>>>>>
+]<<<<]>+++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++>[-<-
+>]<[-]<<<+<[>-]>[-<<<<<<<<<<[-
+>>>>>>>>>>>>>>>>+>+<<<<<<<<<<<<<<<<<]>>>>>>>>>>>>>>>>>[-
+<<<<<<<<<<<<<<<<<+>>>>>>>>>>>>>>>>>]<[[-]<<<<<<<<<<<<<<[-
+>>>>>>>>>>>>>>>+>+<<<<<<<<<<<<<<<<]>>>>>>>>>>>>>>>>[-
+<<<<<<<<<<<<<<<<+>>>>>>>>>>>>>>>>]<[[-]<<<++++++++++++++++>>>]<<<<<<<<<<<<<<[-
+>>>>>>>>>>>+>>>+<<<<<<<<<<<<<<]>>>>>>>>>>>>>>[-
+<<<<<<<<<<<<<<+>>>>>>>>>>>>>>]<]<<<<<<<<<<<<<<[-
+>>>>>>>>>>>>>>+>+<<<<<<<<<<<<<<<]>>>>>>>>>>>>>>>[-
+<<<<<<<<<<<<<<<+>>>>>>>>>>>>>>>]<[[-]<<<<<<<<<<<<<<<[-
+>>>>>>>>>>>>>+>>>+<<<<<<<<<<<<<<<<]>>>>>>>>>>>>>>>>[-
+<<<<<<<<<<<<<<<<+>>>>>>>>>>>>>>>>]<]++++++++++++++++<<<<<<<<<<<<<<<[-
+>>>>>>>>>>>>>>>>>>>>>+>+<<<<<<<<<<<<<<<<<<<<<<]>>>>>>>>>>>>>>>>>>>>>>[-
+<<<<<<<<<<<<<<<<<<<<<<+>>>>>>>>>>>>>>>>>>>>>>]<<<<<<<<<<<<<<<<<<<<[-
+>>>>>>>>>>>>>>>>>>+>>+<<<<<<<<<<<<<<<<<<<<]>>>>>>>>>>>>>>>>>>>>[-
+<<<<<<<<<<<<<<<<<<<<+>>>>>>>>>>>>>>>>>>>>]<[-<[-<<+>>>>+<<]>>[-<<+>>]<<<<[-<<<-
+>+<[>-]>[-<++++++++++++++++>>>>+<<<>]<<>>>]>>>]<<<<<<[-]>>>>>[-]<[-
+<<<<<<+>>>>>>]<<<<<<<<<<<<<<<<<<<<<<<<<<<<<[-
+>>>>>>>>>>>>>>>>>>>>>>>>+>+<<<<<<<<<<<<<<<<<<<<<<<<<]>>>>>>>>>>>>>>>>>>>>>>>>>[
+-<<<<<<<<<<<<<<<<<<<<<<<<<+>>>>>>>>>>>>>>>>>>>>>>>>>]<<<<<<<<<<<<<<<<<<<<<<<[-
+>>>>>>>>>>>>>>>>>>>>>>>+>+<<<<<<<<<<<<<<<<<<<<<<<<]>>>>>>>>>>>>>>>>>>>>>>>>[-
+<<<<<<<<<<<<<<<<<<<<<<<<+>>>>>>>>>>>>>>>>>>>>>>>>]<[[-]>+<<[->>-<<]>>[-
+<<+>>]<]<[->+>+<<]>>[-<<+>>]<[[-]<<[-<++>]>>]<<[-<<++>>]>[-
+]<<<<<<<<<<<<<<<<<<<<<<<<<<<<[-
+>>>>>>>>>>>>>>>>>>>>>>>>>>>>>+>+<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<]>>>>>>>>>>>>>>>>
+>>>>>>>>>>>>>>[-
+<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<+>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>]<[[-
+]<<<<<<<<<<<<<<<<<<<<<<<<<<<<[-
+>>>>>>>>>>>>>>>>>>>>>>>>>+>>>>+<<<<<<<<<<<<<<<<<<<<<<<<<<<<<]>>>>>>>>>>>>>>>>>>
+>>>>>>>>>>>[-
+<<<<<<<<<<<<<<<<<<<<<<<<<<<<<+>>>>>>>>>>>>>>>>>>>>>>>>>>>>>]<]+<<<<<<<<<<<<<<<<
+<<<<<<<<<<<<<[-
+>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>+>+<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<]>>>>>>>>>>>>>>
+>>>>>>>>>>>>>>>>>[-
+<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<+>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>]<[[-]<[-]>]<[-
+>+>+<<]>>[-<<+>>]<[[-]<<<<<<<<<<<<<<<<<<<<<<<<<<<<<[-
+>>>>>>>>>>>>>>>>>>>>>>>>+>>>>>>+<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<]>>>>>>>>>>>>>>>>
+>>>>>>>>>>>>>>[-
+<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<+>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>]<]<[-
+]<<<<<<<<<<<<<<<<<<<<<<<[-]>[-]>>>>>>>>>>>>>>>>>>[->>>>+<<<<]>[-
+>>>>>>+<<<<<<]>>>>>>>>>+<<<<<+<[>-]>[->>>>>[-]<<<<<>]<<>>>>+<[>-]>[->>[-
+]<<>]<<>>>[<<<<<<->>>-<<+<[>-]>[->>>>>[-]<<<<<>]<<>>>>+<[>-]>[->>[-
+]<<>]<<>>>]<<<[->>>>+>+<<<<<]>>>>>[-<<<<<+>>>>>]<[[-
+]<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<+>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>]<<<<<<<[-
+<<<<<<<<<<<<<<<<<<<<<<+>>>>>>>>>>>>>>>>>>>>>>]>>>[-
+<<<<<<<<<<<<<<<<<<<<<<<<<+>>>>>>>>>>>>>>>>>>>>>>>>>]<<<<<<<<<<<<<<<<<<<<<<<<<<<
+<<<[-
+>>>>>>>>>>>>>>>>>>>>>>>>>>>+>+<<<<<<<<<<<<<<<<<<<<<<<<<<<<]>>>>>>>>>>>>>>>>>>>>
+>>>>>>>>[-<<<<<<<<<<<<<<<<<<<<<<<<<<<<+>>>>>>>>>>>>>>>>>>>>>>>>>>>>]<[[-
+]<<<<<<<<<<<<<<<<<<<<<<<<<<[-
+>>>>>>>>>>>>>>>>>>+>>>>>>>>>+<<<<<<<<<<<<<<<<<<<<<<<<<<<]>>>>>>>>>>>>>>>>>>>>>>
+>>>>>[-
+<<<<<<<<<<<<<<<<<<<<<<<<<<<+>>>>>>>>>>>>>>>>>>>>>>>>>>>]<]+<<<<<<<<<<<<<<<<<<<<
+<<<<<<<[-
+>>>>>>>>>>>>>>>>>>>>>>>>>>>>+>+<<<<<<<<<<<<<<<<<<<<<<<<<<<<<]>>>>>>>>>>>>>>>>>>
+>>>>>>>>>>>[-<<<<<<<<<<<<<<<<<<<<<<<<<<<<<+>>>>>>>>>>>>>>>>>>>>>>>>>>>>>]<[[-
+]<[-]>]<[->+>+<<]>>[-<<+>>]<[[-]<<<<<<<<<<<<<<<<<<<<<<<<<<<[-
+>>>>>>>>>>>>>>>>>+>>>>>>>>>>>+<<<<<<<<<<<<<<<<<<<<<<<<<<<<]>>>>>>>>>>>>>>>>>>>>
+>>>>>>>>[-<<<<<<<<<<<<<<<<<<<<<<<<<<<<+>>>>>>>>>>>>>>>>>>>>>>>>>>>>]<]<[-
+]<<<<<<<<<<<<<<<<<<<<<<<<<[-]>[-]>>>>>>>>>>>>>>>[->>>>>>>>>+<<<<<<<<<]>[-
+>>>>>>>>>>>+<<<<<<<<<<<]>>>>>>>>>>>>>>+<<<<<+<[>-]>[->>>>>[-]<<<<<>]<<>>>>+<[>-
+]>[->>[-]<<>]<<>>>[<<<<<<->>>-<<+<[>-]>[->>>>>[-]<<<<<>]<<>>>>+<[>-]>[->>[-
+]<<>]<<>>>]<<<[->>>>+>+<<<<<]>>>>>[-<<<<<+>>>>>]<[[-
+]<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<<+>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>]<<<<<<<[-
+<<<<<<<<<<<<<<<<<<<<<<<<+>>>>>>>>>>>>>>>>>>>>>>>>]>>>[-
+<<<<<<<<<<<<<<<<<<<<<<<<<<<+>>>>>>>>>>>>>>>>>>>>>>>>>>>]<<<<<<<<<<<<<<<<<<<<<<<
+<+>>>>+<<<->+<[>-]>[->>[-]<<>]<<>>>>>>>>>>>>>>>]<<[-]<<[-]>[-]<<<>]<<[-]<<<<[-
+]>[-]>[-]>[-]<<<<]<<<<[->>>>>+>>+<<<<<<<]>>>>>>>[-
+<<<<<<<+>>>>>>>]<++++++++++++++++++++++++++++++++<[->++++++++++++++<[->--<[-
+>+<[->+++++++++++++<[->+<[->++<[->------------------<[->-<[-
+>+++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++<[-
+>+++++++++<[->-----------------------------------------<[->--------------------
+----------------------<[->--<[->+++++++++++++++++++++++++++++<[->--------------
+------------<]]]]]]]]]]]]]]]>.[-]<<<<<<[-]>[-]<<<<<[-]>[-]>[-]>[-]<<<<<[-]>[-
+]<<<<<<+<<<<]>>>>>>>[-]>[-]>>>>>>>>>>>>++++++++++.[-]<<<<<<<<<<<<<<<<<<<++<<]
@@ -0,0 +1,374 @@
+//! Tests of command line options that the conformance tests don't cover.
+
+#[allow(dead_code)]
+mod common;
+
+use std::fs::{self, File};
+use std::process::{Command, Output};
+
+use common::run_with_input;
+
+fn brain_rust(args: &[&str], input: &[u8]) -> Output {
+    run_with_input(
+        Command::new(env!("CARGO_BIN_EXE_brain_rust")).args(args),
+        input.to_vec(),
+    )
+    .unwrap()
+}
+
+#[test]
+fn test_expression() {
+    let output = brain_rust(&["-e", "++++++++[>++++++++<-]>+.", "-e", "+.+."], b"");
+    assert!(output.status.success());
+    assert_eq!(output.stdout, b"ABC");
+}
+
+#[test]
+fn test_expression_parse_error() {
+    let output = brain_rust(&["run", "-e", "[", "-e", ""], b"");
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "Error while parsing -e: Missing ] at line 2, column 1\n"
+    );
+}
+
+#[test]
+fn test_program_from_stdin() {
+    let input = std::env::temp_dir().join("brain_rust_cli_stdin.in");
+    fs::write(&input, b"abc").unwrap();
+    let output = brain_rust(&["-", "--input", input.to_str().unwrap()], b",[.,]");
+    fs::remove_file(&input).unwrap();
+    assert!(output.status.success());
+    assert_eq!(output.stdout, b"abc");
+}
+
+#[test]
+fn test_input_string() {
+    let output = brain_rust(
+        &["-e", ",.,[.,]", "--input-string", r"\0a\tb\x41\\\n"],
+        b"ignored",
+    );
+    assert!(output.status.success());
+    assert_eq!(output.stdout, b"\0a\tbA\\\n");
+}
+
+#[test]
+fn test_input_string_bad_escape() {
+    let output = brain_rust(&["-e", ",[.,]", "--input-string", r"\x4"], b"");
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains(r#"\x needs two hex digits, not "4""#));
+}
+
+#[test]
+fn test_output_file() {
+    let path = std::env::temp_dir().join("brain_rust_cli_output.out");
+    let output = brain_rust(&["-e", ",[.,]", "-o", path.to_str().unwrap()], b"ab");
+    let written = fs::read(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+    assert!(output.status.success());
+    assert!(output.stdout.is_empty());
+    assert_eq!(written, b"ab");
+}
+
+#[test]
+fn test_output_format() {
+    let output = brain_rust(
+        &["-e", ",[.,]", "--output-format", "escaped"],
+        b"a\x01\xff\n",
+    );
+    assert_eq!(output.stdout, b"a\\x01\\xff\n");
+    let output = brain_rust(&["-e", ",[.,]", "--output-format", "hex"], b"AB");
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        format!("00000000:  41 42{}AB\n", " ".repeat(45))
+    );
+}
+
+#[test]
+fn test_flush() {
+    for flush in ["newline", "input", "exit"] {
+        let output = brain_rust(&["-e", ",[.,]", "--flush", flush], b"a\nb");
+        assert_eq!(output.stdout, b"a\nb", "--flush {}", flush);
+    }
+}
+
+#[test]
+fn test_exit_codes() {
+    let code = |args: &[&str]| brain_rust(args, b"").status.code();
+    assert_eq!(code(&["-e", "+."]), Some(0));
+    assert_eq!(code(&["-e", "]"]), Some(3));
+    assert_eq!(code(&["missing.bf"]), Some(4));
+    assert_eq!(code(&["-e", "<"]), Some(5));
+    assert_eq!(code(&["-e", "+[]", "--max-steps", "10"]), Some(6));
+    assert_eq!(code(&["-e", "+[>+<]"]), Some(7));
+}
+
+#[test]
+fn test_tape_size() {
+    let mut backends = vec!["interpreter", "bytecode"];
+    if cfg!(feature = "cranelift") {
+        backends.push("cranelift");
+    }
+    for backend in backends {
+        let output = brain_rust(
+            &["--backend", backend, "--tape-size", "1", "-e", "+.>"],
+            b"",
+        );
+        assert_eq!(output.status.code(), Some(5), "{}", backend);
+        assert_eq!(output.stdout, b"\x01", "{}", backend);
+        // the pointer starts on a cell, so there is at least one
+        let output = brain_rust(&["--backend", backend, "--tape-size", "0", "-e", "+."], b"");
+        assert_eq!(output.status.code(), Some(2), "{}", backend);
+        assert!(output.stdout.is_empty(), "{}", backend);
+    }
+}
+
+// reading a directory fails with "is a directory"
+#[cfg(target_os = "linux")]
+#[test]
+fn test_input_error() {
+    let mut backends = vec!["interpreter", "bytecode"];
+    if cfg!(feature = "llvm") {
+        backends.push("llvm");
+    }
+    if cfg!(feature = "cranelift") {
+        backends.push("cranelift");
+    }
+    for backend in backends {
+        let output = Command::new(env!("CARGO_BIN_EXE_brain_rust"))
+            .args(["--backend", backend, "-e", ",."])
+            .stdin(File::open("/").unwrap())
+            .output()
+            .unwrap();
+        assert_eq!(output.status.code(), Some(4), "{}", backend);
+        assert_eq!(
+            String::from_utf8_lossy(&output.stderr),
+            "Error while running -e: Can't read input: is a directory\n"
+        );
+    }
+}
+
+// `/dev/full` fails every write with "no space left"
+#[cfg(target_os = "linux")]
+#[test]
+fn test_output_error() {
+    for backend in ["interpreter", "bytecode"] {
+        let output = brain_rust(
+            &["--backend", backend, "-o", "/dev/full", "-e", "+[.]"],
+            b"",
+        );
+        assert_eq!(output.status.code(), Some(4));
+        assert_eq!(
+            String::from_utf8_lossy(&output.stderr),
+            "Error while running -e: Can't write output: no storage space\n"
+        );
+    }
+
+    // the hex dump writes its incomplete last line only at the end
+    for (format, error) in [
+        (
+            "hex",
+            "Error while writing output: No space left on device (os error 28)\n",
+        ),
+        (
+            "escaped",
+            "Error while running -e: Can't write output: no storage space\n",
+        ),
+    ] {
+        let output = brain_rust(
+            &["--output-format", format, "-o", "/dev/full", "-e", "+."],
+            b"",
+        );
+        assert_eq!(output.status.code(), Some(4), "{}", format);
+        assert_eq!(String::from_utf8_lossy(&output.stderr), error);
+    }
+}
+
+#[test]
+fn test_unsupported_backend_options() {
+    let mut backends = vec![];
+    if cfg!(feature = "llvm") {
+        backends.push(("llvm", "--tape-size"));
+        backends.push(("llvm", "--max-steps"));
+        backends.push(("llvm", "--timeout"));
+    }
+    if cfg!(feature = "cranelift") {
+        backends.push(("cranelift", "--max-steps"));
+        backends.push(("cranelift", "--timeout"));
+    }
+    for (backend, option) in backends {
+        let mut args = vec!["--backend", backend, option, "10", "-e", "+[]"];
+        if option == "--timeout" {
+            args.extend(["--jobs", "1"]);
+        }
+        let output = brain_rust(&args, b"");
+        assert_eq!(output.status.code(), Some(2));
+        assert_eq!(
+            String::from_utf8_lossy(&output.stderr),
+            format!("The {backend} backend doesn't support {option}\n")
+        );
+    }
+}
+
+#[test]
+fn test_keep_going() {
+    let program = std::env::temp_dir().join("brain_rust_cli_keep_going.bf");
+    fs::write(&program, b"+++++[>+++++++++++++<-]>.").unwrap();
+    let program = program.to_str().unwrap();
+
+    let output = brain_rust(&["missing.bf", program], b"");
+    assert_eq!(output.status.code(), Some(4));
+    assert!(output.stdout.is_empty());
+
+    let output = brain_rust(&["--keep-going", "missing.bf", program], b"");
+    fs::remove_file(program).unwrap();
+    assert_eq!(output.status.code(), Some(4));
+    assert_eq!(output.stdout, b"A");
+}
+
+#[test]
+fn test_dump_tape() {
+    let json = "{\"pointer\": 2, \"start\": 1, \"cells\": [2, 0, 3]}\n";
+    let output = brain_rust(&["-e", ">++>>+++<", "--dump-tape-format", "json"], b"");
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stderr), json);
+
+    // the format is a separate option, `--dump-tape` takes no value
+    let output = brain_rust(
+        &[
+            "-e",
+            ">++>>+++<",
+            "--dump-tape",
+            "--dump-tape-format",
+            "json",
+        ],
+        b"",
+    );
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stderr), json);
+    let output = brain_rust(&["-e", ">++>>+++<", "--dump-tape"], b"");
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).starts_with("pointer at cell 2\n"));
+}
+
+#[test]
+fn test_repl() {
+    let output = brain_rust(
+        &["repl", "--input-string", "A"],
+        b"+++\n>++[\n<+>-]\n<,.\n]\n",
+    );
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "pointer at 0, cells 0..5: [3] 0 0 0 0\n\
+         pointer at 1, cells 0..6: 5 [0] 0 0 0 0\n\
+         A\n\
+         pointer at 0, cells 0..5: [65] 0 0 0 0\n"
+    );
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "Error while parsing: Stray ] at line 1, column 1\n"
+    );
+}
+
+#[test]
+fn test_bench() {
+    let output = brain_rust(&["bench", "--runs", "1", "-"], b"+[,.]");
+    assert!(output.status.success());
+    let table = String::from_utf8_lossy(&output.stdout);
+    let rows: Vec<_> = table.lines().collect();
+    assert!(rows[0].starts_with("backend"));
+    assert!(rows[1].starts_with("interpreter "));
+    assert!(rows[2].starts_with("optimized interpreter "));
+    assert!(rows.iter().skip(1).all(|row| row.ends_with('x')));
+
+    let output = brain_rust(&["bench", "--runs", "1", "-"], b"<");
+    assert_eq!(output.status.code(), Some(5));
+    let table = String::from_utf8_lossy(&output.stdout);
+    assert!(table
+        .lines()
+        .nth(1)
+        .unwrap()
+        .ends_with("Pointer moved off the tape"));
+}
+
+#[cfg(feature = "llvm")]
+#[test]
+fn test_jit_cache() {
+    let dir = std::env::temp_dir().join("brain_rust_cli_jit_cache");
+    let _ = fs::remove_dir_all(&dir);
+    let args = [
+        "--llvm",
+        "--jit-cache",
+        dir.to_str().unwrap(),
+        "-e",
+        "+++++[>+++++++++++++<-]>.,.",
+    ];
+
+    let output = brain_rust(&args, b"b");
+    assert_eq!(output.stdout, b"Ab");
+    assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+    // the second run loads the program from the cache
+    let output = brain_rust(&args, b"c");
+    assert_eq!(output.stdout, b"Ac");
+    assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_jobs() {
+    let dir = std::env::temp_dir().join("brain_rust_cli_jobs");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir(&dir).unwrap();
+    let files = [
+        ("echo.bf", ",[.,]"),
+        ("echo.in", "hi"),
+        ("left.bf", "<"),
+        ("forever.bf", "+[[-]+]"),
+        ("hello.bf", "+++++[>+++++++++++++<-]>."),
+    ];
+    for (name, contents) in files {
+        fs::write(dir.join(name), contents).unwrap();
+    }
+    let path = |name: &str| dir.join(name).to_str().unwrap().to_string();
+    let (echo, left, forever, hello) = (
+        path("echo.bf"),
+        path("left.bf"),
+        path("forever.bf"),
+        path("hello.bf"),
+    );
+
+    // all files are run anyway
+    let output = brain_rust(&["--jobs", "3", "--keep-going", &hello], b"");
+    assert_eq!(output.status.code(), Some(2));
+
+    let output = brain_rust(
+        &[
+            "--jobs",
+            "3",
+            "--timeout",
+            "1",
+            &echo,
+            &left,
+            &forever,
+            &hello,
+        ],
+        b"",
+    );
+    fs::remove_dir_all(&dir).unwrap();
+    // the first failure decides the exit code
+    assert_eq!(output.status.code(), Some(5));
+    // output comes in the order of the files, each with its own input
+    assert_eq!(output.stdout, b"hiA");
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        format!(
+            "Error while running {left}: Pointer moved off the tape\n\
+             passed     {echo}\n\
+             failed     {left}\n\
+             timed out  {forever}\n\
+             passed     {hello}\n\
+             2 passed, 1 failed, 1 timed out\n"
+        )
+    );
+}
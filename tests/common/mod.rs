@@ -0,0 +1,60 @@
+//! Helpers for running the programs below `test_cases/`.
+
+use std::fs;
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+use std::thread;
+
+/// All programs below `test_cases/`, sorted.
+pub fn programs() -> Vec<PathBuf> {
+    let mut programs = vec![];
+    find_programs(
+        Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/test_cases")),
+        &mut programs,
+    );
+    programs.sort();
+    assert!(!programs.is_empty());
+    programs
+}
+
+fn find_programs(dir: &Path, programs: &mut Vec<PathBuf>) {
+    for entry in fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            find_programs(&path, programs);
+        } else if path.extension() == Some("bf".as_ref()) {
+            programs.push(path);
+        }
+    }
+}
+
+/// The contents of the file next to `program` with the given extension, or
+/// nothing if there is none.
+pub fn read_expectation(program: &Path, extension: &str) -> Vec<u8> {
+    fs::read(program.with_extension(extension)).unwrap_or_default()
+}
+
+/// The expected exit code of `program`.
+pub fn expected_code(program: &Path) -> i32 {
+    let code = String::from_utf8(read_expectation(program, "code")).unwrap();
+    code.trim().parse().unwrap_or(0)
+}
+
+/// Runs the command with `input` on stdin and collects its output.
+pub fn run_with_input(command: &mut Command, input: Vec<u8>) -> Result<Output, String> {
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| e.to_string())?;
+    let mut stdin = child.stdin.take().unwrap();
+    let writer = thread::spawn(move || match stdin.write_all(&input) {
+        Err(e) if e.kind() != ErrorKind::BrokenPipe => Err(e),
+        _ => Ok(()),
+    });
+    let result = child.wait_with_output().map_err(|e| e.to_string())?;
+    writer.join().unwrap().map_err(|e| e.to_string())?;
+    Ok(result)
+}
@@ -0,0 +1,199 @@
+//! Translates every valid program below `test_cases/` into each target
+//! language, builds the result and checks it against the same expectations as
+//! the conformance tests. Targets whose tools are missing are skipped.
+
+mod common;
+
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+use common::{expected_code, programs, read_expectation, run_with_input};
+
+struct Target {
+    name: &'static str,
+    extension: &'static str,
+    /// Builds the executable from the source file.
+    build: fn(&Path, &Path) -> Command,
+    /// Runs the program given the source file and the executable.
+    run: fn(&Path, &Path) -> Command,
+}
+
+fn targets() -> Vec<Target> {
+    let mut targets = vec![
+        Target {
+            name: "c",
+            extension: "c",
+            build: |source, executable| {
+                let mut command = Command::new("cc");
+                command
+                    .args(["-O1", "-Wall", "-Wextra", "-Werror", "-o"])
+                    .arg(executable)
+                    .arg(source);
+                command
+            },
+            run: |_, executable| Command::new(executable),
+        },
+        Target {
+            name: "rust",
+            extension: "rs",
+            build: |source, executable| {
+                let mut command = Command::new("rustc");
+                command
+                    .args(["--edition", "2021", "-O", "-D", "warnings", "-o"])
+                    .arg(executable)
+                    .arg(source);
+                command
+            },
+            run: |_, executable| Command::new(executable),
+        },
+        Target {
+            name: "rust-lib",
+            extension: "rs",
+            // Includes the library into a program calling it, like a build
+            // script would.
+            build: |source, executable| {
+                let main = source.with_extension("main.rs");
+                fs::write(
+                    &main,
+                    format!(
+                        concat!(
+                            "mod program {{\n    include!({:?});\n}}\n\n",
+                            "fn main() {{\n",
+                            "    if let Err(e) = program::run(std::io::stdin(), std::io::stdout()) {{\n",
+                            "        eprintln!(\"{{}}\", e);\n",
+                            "        std::process::exit(1);\n",
+                            "    }}\n",
+                            "}}\n",
+                        ),
+                        source
+                    ),
+                )
+                .unwrap();
+                let mut command = Command::new("rustc");
+                command
+                    .args(["--edition", "2021", "-O", "-D", "warnings"])
+                    .args(["--crate-name", "main", "-o"])
+                    .arg(executable)
+                    .arg(main);
+                command
+            },
+            run: |_, executable| Command::new(executable),
+        },
+        Target {
+            name: "wasm",
+            extension: "wasm",
+            // Only validates the module, which node runs directly.
+            build: |source, _| {
+                let mut command = Command::new("node");
+                command
+                    .arg("-e")
+                    .arg("new WebAssembly.Module(require('fs').readFileSync(process.argv[1]))")
+                    .arg(source);
+                command
+            },
+            run: |source, _| {
+                let mut command = Command::new("node");
+                command
+                    .arg(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/run_wasm.mjs"))
+                    .arg(source);
+                command
+            },
+        },
+    ];
+    if cfg!(all(target_arch = "x86_64", target_os = "linux")) {
+        targets.push(Target {
+            name: "x86-64",
+            extension: "s",
+            build: |source, executable| {
+                let mut command = Command::new("cc");
+                command
+                    .args(["-nostdlib", "-static", "-o"])
+                    .arg(executable)
+                    .arg(source);
+                command
+            },
+            run: |_, executable| Command::new(executable),
+        });
+    }
+    targets
+}
+
+fn check(program: &Path, target: &Target, dir: &Path) -> Result<(), String> {
+    let stem = program.file_stem().unwrap();
+    let source = dir.join(stem).with_extension(target.extension);
+    let executable = dir.join(stem);
+
+    let result = Command::new(env!("CARGO_BIN_EXE_brain_rust"))
+        .args(["compile", "--target", target.name, "--output"])
+        .arg(&source)
+        .arg(program)
+        .output()
+        .map_err(|e| e.to_string())?;
+    if !result.status.success() || !result.stderr.is_empty() {
+        return Err(format!(
+            "compiling failed:\n{}",
+            String::from_utf8_lossy(&result.stderr)
+        ));
+    }
+
+    let result = (target.build)(&source, &executable)
+        .output()
+        .map_err(|e| e.to_string())?;
+    if !result.status.success() {
+        return Err(format!(
+            "building failed:\n{}",
+            String::from_utf8_lossy(&result.stderr)
+        ));
+    }
+
+    let result = run_with_input(
+        &mut (target.run)(&source, &executable),
+        read_expectation(program, "in"),
+    )?;
+    let mut mismatches = vec![];
+    if result.stdout != read_expectation(program, "out") {
+        mismatches.push(format!(
+            "output was:\n{}",
+            String::from_utf8_lossy(&result.stdout)
+        ));
+    }
+    if result.status.code() != Some(expected_code(program)) {
+        mismatches.push(format!(
+            "exit code was {:?} instead of {}",
+            result.status.code(),
+            expected_code(program)
+        ));
+    }
+    if mismatches.is_empty() {
+        Ok(())
+    } else {
+        Err(mismatches.join("\n"))
+    }
+}
+
+#[test]
+fn test_compile() {
+    let mut failures = vec![];
+    for target in targets() {
+        let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join(target.name);
+        fs::create_dir_all(&dir).unwrap();
+        if (target.build)(&dir.join("missing"), &dir.join("missing"))
+            .output()
+            .is_err()
+        {
+            eprintln!("skipping {}: tools not found", target.name);
+            continue;
+        }
+        for program in programs() {
+            // programs that don't parse have nothing to compile
+            if !read_expectation(&program, "err").is_empty() {
+                continue;
+            }
+            if let Err(e) = check(&program, &target, &dir) {
+                failures.push(format!("{} to {}: {}", program.display(), target.name, e));
+            }
+        }
+    }
+    assert!(failures.is_empty(), "\n{}", failures.join("\n\n"));
+}
@@ -0,0 +1,87 @@
+//! Runs every program below `test_cases/` on every backend and optimization
+//! level. For a program `NAME.bf`, `NAME.in` is fed to it as input, its output
+//! and error output must match `NAME.out` and `NAME.err`, and its exit code
+//! must match the one in `NAME.code`. Missing files stand for no input, no
+//! output and exit code 0.
+
+mod common;
+
+use std::path::Path;
+use std::process::Command;
+
+use common::{expected_code, programs, read_expectation, run_with_input};
+
+fn backends() -> Vec<(&'static str, &'static [&'static str])> {
+    let mut backends: Vec<(&str, &[&str])> = vec![
+        ("interpreter", &["--no-optimize"]),
+        ("optimized interpreter", &[]),
+        ("optimized interpreter via run", &["run"]),
+        (
+            "bytecode interpreter",
+            &["--backend", "bytecode", "--no-optimize"],
+        ),
+        ("optimized bytecode interpreter", &["--backend", "bytecode"]),
+    ];
+    if cfg!(feature = "llvm") {
+        backends.push(("LLVM JIT", &["--backend", "llvm", "--no-optimize"]));
+        backends.push(("optimized LLVM JIT", &["--backend", "llvm"]));
+    }
+    if cfg!(feature = "cranelift") {
+        backends.push((
+            "Cranelift JIT",
+            &["--backend", "cranelift", "--no-optimize"],
+        ));
+        backends.push(("optimized Cranelift JIT", &["--backend", "cranelift"]));
+    }
+    backends
+}
+
+fn check(program: &Path, flags: &[&str]) -> Result<(), String> {
+    let expected_code = expected_code(program);
+    let result = run_with_input(
+        Command::new(env!("CARGO_BIN_EXE_brain_rust"))
+            .args(flags)
+            .arg(program.file_name().unwrap())
+            .current_dir(program.parent().unwrap()),
+        read_expectation(program, "in"),
+    )?;
+
+    let mut mismatches = vec![];
+    if result.stdout != read_expectation(program, "out") {
+        mismatches.push(format!(
+            "output was:\n{}",
+            String::from_utf8_lossy(&result.stdout)
+        ));
+    }
+    if result.stderr != read_expectation(program, "err") {
+        mismatches.push(format!(
+            "error output was:\n{}",
+            String::from_utf8_lossy(&result.stderr)
+        ));
+    }
+    if result.status.code() != Some(expected_code) {
+        mismatches.push(format!(
+            "exit code was {:?} instead of {}",
+            result.status.code(),
+            expected_code
+        ));
+    }
+    if mismatches.is_empty() {
+        Ok(())
+    } else {
+        Err(mismatches.join("\n"))
+    }
+}
+
+#[test]
+fn test_conformance() {
+    let mut failures = vec![];
+    for program in &programs() {
+        for (backend, flags) in backends() {
+            if let Err(e) = check(program, flags) {
+                failures.push(format!("{} on {}: {}", program.display(), backend, e));
+            }
+        }
+    }
+    assert!(failures.is_empty(), "\n{}", failures.join("\n\n"));
+}
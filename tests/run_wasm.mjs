@@ -0,0 +1,20 @@
+// Runs a module from `brain_rust compile --target wasm` on stdin and stdout,
+// providing getchar and putchar the way a web page would.
+import fs from "node:fs";
+
+const input = fs.readFileSync(0);
+let position = 0;
+const output = [];
+const module = new WebAssembly.Module(fs.readFileSync(process.argv[2]));
+const instance = new WebAssembly.Instance(module, {
+  env: {
+    getchar: () => (position < input.length ? input[position++] : -1),
+    putchar: (c) => output.push(c),
+  },
+});
+const result = instance.exports.run();
+process.stdout.write(Buffer.from(output));
+if (result !== 0) {
+  console.error("Pointer moved off the tape");
+  process.exitCode = 1;
+}